@@ -1,7 +1,5 @@
 use std::io::stdin;
 
-use aoc_2019_07;
-
 fn main() {
     let mut buffer = String::new();
     stdin().read_line(&mut buffer).unwrap();
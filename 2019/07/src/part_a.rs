@@ -1,5 +1,3 @@
-use std::{cell::RefCell, rc::Rc};
-
 use intcode_computer::Computer;
 
 const AMPLIFIERS_COUNT: usize = 5;
@@ -32,10 +30,9 @@ impl MaxSequenceFinder {
             }
 
             let input = vec![i as isize, last_input_signal];
-            let mut computer =
-                Computer::new(self.computer_memory.clone(), Rc::new(RefCell::new(input)));
-            computer.run_till_halt();
-            let output_signal = *computer.output().get(0).unwrap();
+            let mut computer = Computer::new(self.computer_memory.clone(), input);
+            computer.run_till_halt().expect("amplifier failed");
+            let output_signal = *computer.output().first().unwrap();
 
             self.phase_sequence.push(i);
             self.used_phase_settings[i] = true;
@@ -1,6 +1,7 @@
-use std::{cell::RefCell, rc::Rc};
-
-use intcode_computer::{Computer, Instruction};
+use intcode_computer::{
+    network::{Network, Topology},
+    Computer,
+};
 
 use crate::unique_number_sequence_generator::UniqueNumberSequenceGenerator;
 
@@ -11,45 +12,20 @@ pub fn part_b(computer_memory: Vec<isize>) -> isize {
     let mut max_output = isize::MIN;
 
     for seq in sequences {
-        let inputs: Vec<Rc<RefCell<Vec<isize>>>> = seq
+        let computers = seq
             .into_iter()
-            .map(|s| Rc::new(RefCell::new(vec![s as isize])))
-            .collect();
-
-        inputs.last().unwrap().borrow_mut().push(0);
-        let mut computers = (0..AMPLIFIERS_COUNT)
-            .map(|i| {
-                Computer::with_output(
-                    computer_memory.clone(),
-                    Rc::clone(&inputs[(AMPLIFIERS_COUNT + i - 1) % AMPLIFIERS_COUNT]),
-                    Rc::clone(&inputs[i]),
-                )
-            })
+            .map(|s| Computer::new(computer_memory.clone(), vec![s as isize]))
             .collect::<Vec<_>>();
 
-        let mut computer_i = 0;
-
-        loop {
-            let computer = computers.get_mut(computer_i).unwrap();
-
-            let halted = loop {
-                let instr = computer.parse_and_exec_once();
-                match instr {
-                    Instruction::Halt => break true,
-                    Instruction::WriteOutput { .. } => {
-                        computer_i = (computer_i + 1) % AMPLIFIERS_COUNT;
-                        break false;
-                    }
-                    _ => {}
-                };
-            };
-
-            if halted {
-                break;
-            }
-        }
+        let mut network = Network::new(computers, Topology::Ring);
+        network.send(0, 0);
+        network.run_till_halt().expect("amplifier failed");
 
-        let output = *computers.last().unwrap().output().last().unwrap();
+        let output = *network
+            .machine(AMPLIFIERS_COUNT - 1)
+            .output()
+            .last()
+            .unwrap();
         if output > max_output {
             max_output = output;
         }
@@ -31,10 +31,9 @@ impl UniqueNumberSequenceGenerator {
 
     fn get_next_sequence(&mut self) -> bool {
         loop {
-            self.num_sequence.pop().and_then(|s| {
+            if let Some(s) = self.num_sequence.pop() {
                 self.used_numbers[s] = false;
-                Some(s)
-            });
+            }
 
             loop {
                 let possible_numbers = match self.possible_numbers_for_places.last_mut() {
@@ -1,21 +1,17 @@
-use std::{cell::RefCell, fs::read_to_string, rc::Rc};
+use std::fs::read_to_string;
 
-use intcode_computer::Computer;
+use intcode_computer::{program, Computer};
 
 fn main() {
     let input = read_to_string("input.txt").unwrap();
-    let program: Vec<isize> = input
-        .trim()
-        .split(",")
-        .map(|x| x.parse().expect(&format!("Cannot parse {}", x)))
-        .collect();
-    let mut computer = Computer::new(program.clone(), Rc::new(RefCell::new(vec![1])));
-    computer.run_till_halt();
+    let program = program::parse_from_string(&input).expect("cannot parse program");
+    let mut computer = Computer::new(program.clone(), vec![1]);
+    computer.run_till_halt().expect("program failed");
 
     println!("Result A: {:?}", computer.output());
 
-    let mut computer = Computer::new(program, Rc::new(RefCell::new(vec![2])));
-    computer.run_till_halt();
+    let mut computer = Computer::new(program, vec![2]);
+    computer.run_till_halt().expect("program failed");
 
     println!("Result B: {:?}", computer.output());
 }
@@ -0,0 +1,291 @@
+//! Export of the painted hull as PBM and PNG images, and as an animated PNG showing the robot
+//! painting.
+//!
+//! Images are oriented like `map_printer::print_map`, with growing `y` going up. Every panel is
+//! drawn as a `scale` by `scale` square.
+
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+};
+
+use png::{BitDepth, ColorType, Encoder};
+
+use crate::{
+    map_printer::get_bounds,
+    move_emulator::{Color, Paint},
+    point::Point,
+};
+
+/// Pixels per line of a plain PBM. With a space after every pixel but the last, lines stay
+/// within the 70 characters allowed.
+const PBM_PIXELS_PER_LINE: usize = 35;
+
+const BLACK: u8 = 0;
+const WHITE: u8 = 255;
+/// The panel the robot stands on in animation frames.
+const ROBOT: u8 = 128;
+
+/// Grayscale pixels of panels, one byte per panel.
+#[derive(Clone)]
+struct Panels {
+    from: Point,
+    width: usize,
+    height: usize,
+    values: Vec<u8>,
+}
+
+impl Panels {
+    /// Black panels covering the bounds of `points`.
+    fn covering<'a>(points: impl Iterator<Item = &'a Point>) -> Self {
+        let (from, to) = get_bounds(points);
+        let width = (to.x - from.x + 1) as usize;
+        let height = (to.y - from.y + 1) as usize;
+
+        Self {
+            from,
+            width,
+            height,
+            values: vec![BLACK; width * height],
+        }
+    }
+
+    fn set(&mut self, point: Point, value: u8) {
+        let column = (point.x - self.from.x) as usize;
+        let row = self.height - 1 - (point.y - self.from.y) as usize;
+        self.values[row * self.width + column] = value;
+    }
+
+    fn paint(&mut self, point: Point, color: Color) {
+        let value = match color {
+            Color::Black => BLACK,
+            Color::White => WHITE,
+        };
+        self.set(point, value);
+    }
+
+    /// Rows of pixels, with every panel repeated `scale` times in both directions.
+    fn scaled_rows(&self, scale: usize) -> impl Iterator<Item = Vec<u8>> + '_ {
+        self.values.chunks(self.width).flat_map(move |row| {
+            let pixels: Vec<u8> = row
+                .iter()
+                .flat_map(|&value| std::iter::repeat_n(value, scale))
+                .collect();
+            std::iter::repeat_n(pixels, scale)
+        })
+    }
+
+    fn scaled_data(&self, scale: usize) -> Vec<u8> {
+        self.scaled_rows(scale).flatten().collect()
+    }
+
+    fn encoder<W: Write>(&self, out: W, scale: usize) -> Encoder<'static, W> {
+        let mut encoder = Encoder::new(
+            out,
+            (self.width * scale) as u32,
+            (self.height * scale) as u32,
+        );
+        encoder.set_color(ColorType::Grayscale);
+        encoder.set_depth(BitDepth::Eight);
+
+        encoder
+    }
+}
+
+fn check_scale(scale: usize) -> io::Result<()> {
+    if scale == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the scale must be at least 1",
+        ));
+    }
+
+    Ok(())
+}
+
+fn painted_panels(map: &HashMap<Point, Color>) -> Panels {
+    let mut panels = Panels::covering(map.keys());
+    for (&point, &color) in map {
+        panels.paint(point, color);
+    }
+
+    panels
+}
+
+/// Writes a plain (ASCII) portable bitmap, where white panels are 0 and black ones are 1. Long
+/// rows of pixels are wrapped over several lines.
+pub fn write_pbm(map: &HashMap<Point, Color>, scale: usize, mut out: impl Write) -> io::Result<()> {
+    check_scale(scale)?;
+    let panels = painted_panels(map);
+    writeln!(out, "P1")?;
+    writeln!(out, "{} {}", panels.width * scale, panels.height * scale)?;
+
+    for row in panels.scaled_rows(scale) {
+        for line in row.chunks(PBM_PIXELS_PER_LINE) {
+            let bits: Vec<&str> = line
+                .iter()
+                .map(|&value| if value == WHITE { "0" } else { "1" })
+                .collect();
+            writeln!(out, "{}", bits.join(" "))?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn write_png(map: &HashMap<Point, Color>, scale: usize, out: impl Write) -> io::Result<()> {
+    check_scale(scale)?;
+    let panels = painted_panels(map);
+    let mut writer = panels.encoder(out, scale).write_header()?;
+    writer.write_image_data(&panels.scaled_data(scale))?;
+
+    Ok(writer.finish()?)
+}
+
+/// Writes an animated PNG replaying the paints in order, with `paints_per_frame` paints per
+/// frame and `frame_delay_ms` milliseconds between frames. The robot is drawn in gray on the
+/// panel it moves to next.
+pub fn write_animated_png(
+    paints: &[Paint],
+    scale: usize,
+    paints_per_frame: usize,
+    frame_delay_ms: u16,
+    out: impl Write,
+) -> io::Result<()> {
+    check_scale(scale)?;
+    let mut panels = Panels::covering(
+        paints
+            .iter()
+            .flat_map(|paint| [&paint.position, &paint.robot_after]),
+    );
+    let chunks: Vec<&[Paint]> = paints.chunks(paints_per_frame.max(1)).collect();
+
+    let mut encoder = panels.encoder(out, scale);
+    encoder.set_animated(chunks.len().max(1) as u32, 0)?;
+    encoder.set_frame_delay(frame_delay_ms, 1000)?;
+    let mut writer = encoder.write_header()?;
+
+    if chunks.is_empty() {
+        writer.write_image_data(&panels.scaled_data(scale))?;
+    }
+    for (i, chunk) in chunks.iter().enumerate() {
+        for paint in chunk.iter() {
+            panels.paint(paint.position, paint.color);
+        }
+
+        if i + 1 < chunks.len() {
+            let mut frame = panels.clone();
+            frame.set(chunk.last().unwrap().robot_after, ROBOT);
+            writer.write_image_data(&frame.scaled_data(scale))?;
+        } else {
+            writer.write_image_data(&panels.scaled_data(scale))?;
+        }
+    }
+
+    Ok(writer.finish()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map() -> HashMap<Point, Color> {
+        vec![
+            (Point { x: 0, y: 0 }, Color::White),
+            (Point { x: 1, y: 0 }, Color::Black),
+            (Point { x: 1, y: 1 }, Color::White),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn writes_scaled_pbm() {
+        let mut out = Vec::new();
+        write_pbm(&map(), 2, &mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "P1\n4 4\n1 1 0 0\n1 1 0 0\n0 0 1 1\n0 0 1 1\n"
+        );
+    }
+
+    #[test]
+    fn wraps_long_pbm_rows() {
+        let mut out = Vec::new();
+        write_pbm(&map(), 20, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines[1], "40 40");
+        assert!(lines.iter().all(|line| line.len() <= 70));
+        // Every row of 40 pixels takes two lines
+        assert_eq!(lines.len(), 2 + 40 * 2);
+        assert_eq!(lines[2], ["1"; 20].join(" ") + " " + &["0"; 15].join(" "));
+        assert_eq!(lines[3], ["0"; 5].join(" "));
+    }
+
+    #[test]
+    fn rejects_zero_scale() {
+        let paints = [Paint {
+            position: Point { x: 0, y: 0 },
+            color: Color::White,
+            robot_after: Point { x: 1, y: 0 },
+        }];
+        let invalid_input = |result: io::Result<()>| result.unwrap_err().kind();
+
+        assert_eq!(
+            invalid_input(write_pbm(&map(), 0, Vec::new())),
+            io::ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            invalid_input(write_png(&map(), 0, Vec::new())),
+            io::ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            invalid_input(write_animated_png(&paints, 0, 1, 100, Vec::new())),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn writes_png() {
+        let mut out = Vec::new();
+        write_png(&map(), 1, &mut out).unwrap();
+
+        let mut reader = png::Decoder::new(out.as_slice()).read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).unwrap();
+
+        assert_eq!((info.width, info.height), (2, 2));
+        assert_eq!(&data[..4], &[BLACK, WHITE, WHITE, BLACK]);
+    }
+
+    #[test]
+    fn writes_one_frame_per_paint() {
+        let paints = vec![
+            Paint {
+                position: Point { x: 0, y: 0 },
+                color: Color::White,
+                robot_after: Point { x: 1, y: 0 },
+            },
+            Paint {
+                position: Point { x: 1, y: 0 },
+                color: Color::White,
+                robot_after: Point { x: 1, y: 1 },
+            },
+        ];
+        let mut out = Vec::new();
+        write_animated_png(&paints, 1, 1, 100, &mut out).unwrap();
+
+        let mut reader = png::Decoder::new(out.as_slice()).read_info().unwrap();
+        assert_eq!(reader.info().animation_control().unwrap().num_frames, 2);
+
+        let mut data = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut data).unwrap();
+        // The robot is on the right of the first painted panel
+        assert_eq!(&data[..4], &[BLACK, BLACK, WHITE, ROBOT]);
+        reader.next_frame(&mut data).unwrap();
+        assert_eq!(&data[..4], &[BLACK, BLACK, WHITE, WHITE]);
+    }
+}
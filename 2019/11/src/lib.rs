@@ -1,4 +1,5 @@
 mod direction;
+pub mod image_export;
 pub mod map_printer;
 pub mod move_emulator;
 pub mod point;
@@ -1,12 +1,20 @@
-use std::fs::read_to_string;
+use std::{
+    env,
+    fs::{read_to_string, File},
+    io::BufWriter,
+};
 
 use aoc_2019_11::{
-    map_printer,
+    image_export, map_printer,
     move_emulator::{Color, MoveEmulator},
     point::Point,
 };
 use intcode_computer::program;
 
+/// Pixels per panel in exported images, unless set with `--scale`.
+const DEFAULT_SCALE: usize = 10;
+const FRAME_DELAY_MS: u16 = 40;
+
 fn main() {
     let input = read_to_string("input.txt").expect("cannot read input");
     let program = program::parse_from_string(&input).expect("cannot parse program");
@@ -22,4 +30,26 @@ fn main() {
 
     println!("Result B:");
     map_printer::print_map(&emulator.map);
+
+    // `--pbm <file>`, `--png <file>` and `--animate <file>` export the registration identifier,
+    // with `--scale <n>` pixels per panel
+    let args: Vec<String> = env::args().skip(1).collect();
+    let value_of = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .map(|i| args.get(i + 1).expect("missing option value").as_str())
+    };
+    let scale = value_of("--scale").map_or(DEFAULT_SCALE, |s| s.parse().expect("invalid scale"));
+    let create = |path: &str| BufWriter::new(File::create(path).expect("cannot create image"));
+
+    if let Some(path) = value_of("--pbm") {
+        image_export::write_pbm(&emulator.map, scale, create(path)).expect("cannot write PBM");
+    }
+    if let Some(path) = value_of("--png") {
+        image_export::write_png(&emulator.map, scale, create(path)).expect("cannot write PNG");
+    }
+    if let Some(path) = value_of("--animate") {
+        image_export::write_animated_png(&emulator.paints, scale, 1, FRAME_DELAY_MS, create(path))
+            .expect("cannot write animation");
+    }
 }
@@ -9,14 +9,15 @@ pub fn print_map(map: &HashMap<Point, Color>) {
         for x in from.x..=to.x {
             let color = map.get(&Point { x, y }).cloned().unwrap_or_default();
 
-            print!("{}", color.to_string());
+            print!("{}", color);
         }
 
-        println!("");
+        println!();
     }
 }
 
-fn get_bounds<'a>(points: impl Iterator<Item = &'a Point>) -> (Point, Point) {
+/// The smallest and largest coordinates of the points, including the origin.
+pub(crate) fn get_bounds<'a>(points: impl Iterator<Item = &'a Point>) -> (Point, Point) {
     let mut from = Point::default();
     let mut to = Point::default();
 
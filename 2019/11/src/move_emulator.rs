@@ -1,16 +1,16 @@
 use std::{
-    cell::RefCell,
     collections::HashMap,
     convert::{TryFrom, TryInto},
-    rc::Rc,
+    fmt::Display,
 };
 
-use intcode_computer::{Computer, Instruction};
+use intcode_computer::{Computer, RunState};
 
 use crate::{direction::Turn, point::Point, robot::Robot};
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
 pub enum Color {
+    #[default]
     Black = 0,
     White = 1,
 }
@@ -27,91 +27,82 @@ impl TryFrom<isize> for Color {
     }
 }
 
-impl Default for Color {
-    fn default() -> Self {
-        Self::Black
-    }
-}
-
-impl Into<isize> for &Color {
-    fn into(self) -> isize {
-        *self as isize
+impl From<&Color> for isize {
+    fn from(color: &Color) -> Self {
+        *color as isize
     }
 }
 
-impl ToString for Color {
-    fn to_string(&self) -> String {
+impl Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Black => " ".into(),
-            Self::White => "X".into(),
+            Self::Black => write!(f, " "),
+            Self::White => write!(f, "X"),
         }
     }
 }
 
+/// A panel painted by the robot.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Paint {
+    pub position: Point,
+    pub color: Color,
+    /// Where the robot moved after painting.
+    pub robot_after: Point,
+}
+
 pub struct MoveEmulator {
     pub map: HashMap<Point, Color>,
+    /// Every paint, in order.
+    pub paints: Vec<Paint>,
     pub robot: Robot,
     pub computer: Computer,
-    input: Rc<RefCell<Vec<isize>>>,
 }
 
 impl MoveEmulator {
     pub fn new(program: Vec<isize>) -> Self {
-        let input = Rc::default();
-        let computer = Computer::new(program, Rc::clone(&input));
-
         Self {
             map: HashMap::new(),
+            paints: Vec::new(),
             robot: Robot::default(),
-            input,
-            computer,
+            computer: Computer::with_empty_input(program),
         }
     }
 
     pub fn run_till_halt(&mut self) {
-        loop {
-            let instr = self.perform_single_move();
-
-            if let Instruction::Halt = instr {
-                break;
-            }
-        }
+        while self.perform_single_move() != RunState::Halted {}
     }
 
-    fn perform_single_move(&mut self) -> Instruction {
+    fn perform_single_move(&mut self) -> RunState {
         let color_under_robot = self
             .map
             .get(&self.robot.position)
-            .unwrap_or(&Color::default())
-            .clone();
-
-        self.input.borrow_mut().push((&color_under_robot).into());
-        let mut turn: Option<Turn> = None;
-        let mut color_to_paint: Option<Color> = None;
-        let instr = loop {
-            let instr = self.computer.parse_and_exec_once();
-            match instr {
-                Instruction::WriteOutput { val } => {
-                    if color_to_paint.is_none() {
-                        color_to_paint = Some(val.try_into().expect("invalid color to paint"));
-                    } else {
-                        turn = Some(val.try_into().expect("invalid turn"));
-                        break instr;
-                    }
-                }
-                Instruction::Halt => break instr,
-                _ => (),
-            }
+            .copied()
+            .unwrap_or_default();
+
+        self.computer
+            .input_mut()
+            .push_back((&color_under_robot).into());
+
+        let color_to_paint: Color = match self.computer.run().expect("robot program failed") {
+            RunState::Output(val) => val.try_into().expect("invalid color to paint"),
+            state => return state,
+        };
+        let state = self.computer.run().expect("robot program failed");
+        let turn: Turn = match state {
+            RunState::Output(val) => val.try_into().expect("invalid turn"),
+            _ => return state,
         };
 
-        match (turn, color_to_paint) {
-            (Some(turn), Some(color_to_paint)) => {
-                self.map.insert(self.robot.position, color_to_paint);
-                self.robot.turn_and_forward(&turn);
-            }
-            _ => {}
-        }
+        let position = self.robot.position;
+        self.map.insert(position, color_to_paint);
+        self.robot.turn_and_forward(&turn);
+        self.paints.push(Paint {
+            position,
+            color: color_to_paint,
+            robot_after: self.robot.position,
+        });
 
-        instr
+        state
     }
 }
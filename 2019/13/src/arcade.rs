@@ -0,0 +1,237 @@
+//! Terminal front end of the arcade cabinet, redrawing only the tiles that changed.
+//!
+//! Controls: left and right arrows move the paddle, Tab switches between the human player and
+//! the AI, `q` or Esc quits.
+
+use std::{
+    io::{self, stdout, Stdout, Write},
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{self, Event, KeyCode, KeyEventKind},
+    execute, queue,
+    style::Print,
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use intcode_computer::RunState;
+
+use crate::{brain::Brain, part_b::PlayerBrain, simulation::Simulation};
+
+/// Time between frames while the AI plays.
+const AI_FRAME_DELAY: Duration = Duration::from_millis(20);
+/// Time between frames while a human plays, long enough to react.
+const HUMAN_FRAME_DELAY: Duration = Duration::from_millis(120);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Human,
+    Ai,
+}
+
+impl Control {
+    fn toggled(self) -> Self {
+        match self {
+            Control::Human => Control::Ai,
+            Control::Ai => Control::Human,
+        }
+    }
+}
+
+pub struct Arcade {
+    simulation: Simulation,
+    brain: Box<dyn Brain>,
+    control: Control,
+}
+
+impl Arcade {
+    /// `program` should already be set to free play, see `part_b::insert_coins`.
+    pub fn new(program: Vec<isize>, control: Control) -> Self {
+        Self::with_brain(program, control, Box::new(PlayerBrain::default()))
+    }
+
+    /// Uses `brain` instead of `PlayerBrain` as the AI.
+    pub fn with_brain(program: Vec<isize>, control: Control, brain: Box<dyn Brain>) -> Self {
+        Self {
+            simulation: Simulation::new(program),
+            brain,
+            control,
+        }
+    }
+
+    pub fn simulation(&self) -> &Simulation {
+        &self.simulation
+    }
+
+    pub fn simulation_mut(&mut self) -> &mut Simulation {
+        &mut self.simulation
+    }
+
+    /// Plays until the game halts or the player quits. Returns the final score.
+    ///
+    /// A headless game is not rendered and has no delay between frames. As nobody can press
+    /// keys then, the AI always plays.
+    pub fn run(&mut self, headless: bool) -> io::Result<isize> {
+        if headless {
+            self.control = Control::Ai;
+            while let Some(movement) = self.tick(None)? {
+                self.simulation.send_movement(movement);
+            }
+        } else {
+            let mut screen = Screen::open()?;
+            while let Some(movement) = self.tick(Some(&mut screen))? {
+                self.simulation.send_movement(movement);
+            }
+        }
+
+        Ok(self.simulation.score)
+    }
+
+    /// Runs the game until the next frame and chooses the movement of the paddle. Returns
+    /// `None` once the game halts or the player quits.
+    fn tick(&mut self, screen: Option<&mut Screen>) -> io::Result<Option<isize>> {
+        let (game_state, run_state) = self.simulation.execute();
+        let screen = match screen {
+            Some(screen) => screen,
+            None if run_state == RunState::Halted => return Ok(None),
+            None => return Ok(Some(self.brain.get_movement(&self.simulation, game_state))),
+        };
+
+        screen.draw(&self.simulation, self.control)?;
+        if run_state == RunState::Halted {
+            screen.wait_for_key()?;
+            return Ok(None);
+        }
+
+        let deadline = Instant::now()
+            + match self.control {
+                Control::Human => HUMAN_FRAME_DELAY,
+                Control::Ai => AI_FRAME_DELAY,
+            };
+        let mut human_movement = 0;
+        while let Some(key) = screen.next_key(deadline)? {
+            match key {
+                KeyCode::Left => human_movement = -1,
+                KeyCode::Right => human_movement = 1,
+                KeyCode::Tab => {
+                    self.control = self.control.toggled();
+                    screen.draw_status(&self.simulation, self.control)?;
+                }
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(None),
+                _ => {}
+            }
+        }
+
+        // The brain keeps track of the ball even while the human plays
+        let ai_movement = self.brain.get_movement(&self.simulation, game_state);
+        Ok(Some(match self.control {
+            Control::Human => human_movement,
+            Control::Ai => ai_movement,
+        }))
+    }
+}
+
+/// The terminal in raw mode on the alternate screen, restored when dropped.
+struct Screen {
+    out: Stdout,
+    /// Row below the board, updated on every frame.
+    status_row: u16,
+}
+
+impl Screen {
+    fn open() -> io::Result<Self> {
+        let mut out = stdout();
+        terminal::enable_raw_mode()?;
+        execute!(out, EnterAlternateScreen, Hide, Clear(ClearType::All))?;
+
+        Ok(Self { out, status_row: 0 })
+    }
+
+    fn draw(&mut self, simulation: &Simulation, control: Control) -> io::Result<()> {
+        for position in simulation.changed_tiles() {
+            let (x, y) = *position;
+            if x < 0 || y < 0 {
+                continue;
+            }
+
+            queue!(
+                self.out,
+                MoveTo(x as u16, y as u16),
+                Print(simulation.tiles[position])
+            )?;
+            self.status_row = self.status_row.max(y as u16 + 2);
+        }
+
+        self.draw_status(simulation, control)
+    }
+
+    fn draw_status(&mut self, simulation: &Simulation, control: Control) -> io::Result<()> {
+        let player = match control {
+            Control::Human => "human",
+            Control::Ai => "AI",
+        };
+
+        queue!(
+            self.out,
+            MoveTo(0, self.status_row),
+            Clear(ClearType::CurrentLine),
+            Print(format!(
+                "Score: {}   Player: {} (Tab to switch, q to quit)",
+                simulation.score, player
+            ))
+        )?;
+        self.out.flush()
+    }
+
+    /// Returns the next key pressed before the deadline.
+    fn next_key(&mut self, deadline: Instant) -> io::Result<Option<KeyCode>> {
+        loop {
+            let now = Instant::now();
+            if now >= deadline || !event::poll(deadline - now)? {
+                return Ok(None);
+            }
+
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Release {
+                    return Ok(Some(key.code));
+                }
+            }
+        }
+    }
+
+    fn wait_for_key(&mut self) -> io::Result<()> {
+        queue!(
+            self.out,
+            MoveTo(0, self.status_row + 1),
+            Print("Game over, press any key to exit")
+        )?;
+        self.out.flush()?;
+
+        loop {
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Release {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(self.out, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::assert_clears_the_board;
+
+    #[test]
+    fn player_brain_clears_the_board() {
+        assert_clears_the_board(Box::new(PlayerBrain::default()), 14096);
+    }
+}
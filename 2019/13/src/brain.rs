@@ -0,0 +1,85 @@
+//! Players that steer the paddle.
+
+use std::cmp::Ordering;
+
+use intcode_computer::RunState;
+
+use crate::simulation::{GameState, Simulation};
+
+/// Frames the lookahead brain runs ahead before giving up on finding where the ball lands.
+pub const DEFAULT_MAX_LOOKAHEAD: usize = 200;
+
+pub trait Brain {
+    /// Chooses the joystick movement (-1, 0 or 1) for the frame the simulation is waiting on.
+    fn get_movement(&mut self, simulation: &Simulation, state: GameState) -> isize;
+}
+
+/// Forks the game and runs the fork ahead with the paddle at rest until the ball reaches the row
+/// above the paddle, then steers to that exact column.
+///
+/// The ball only interacts with the paddle in that row, so the paddle staying still in the fork
+/// does not change where the ball lands.
+pub struct LookaheadBrain {
+    max_lookahead: usize,
+}
+
+impl Default for LookaheadBrain {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_LOOKAHEAD)
+    }
+}
+
+impl LookaheadBrain {
+    /// `max_lookahead` is the number of frames to run ahead. When the ball does not land within
+    /// it, the paddle follows the ball.
+    pub fn new(max_lookahead: usize) -> Self {
+        Self { max_lookahead }
+    }
+
+    /// Column of the ball when it next reaches the row above the paddle.
+    fn landing_x(&self, simulation: &Simulation, state: GameState) -> Option<isize> {
+        let landing_row = state.player_pos.1 - 1;
+        if state.ball_pos.1 == landing_row {
+            return Some(state.ball_pos.0);
+        }
+
+        let mut fork = simulation.fork();
+        for _ in 0..self.max_lookahead {
+            fork.send_movement(0);
+            let (state, run_state) = fork.execute();
+            if run_state == RunState::Halted {
+                return None;
+            }
+            if state.ball_pos.1 == landing_row {
+                return Some(state.ball_pos.0);
+            }
+        }
+
+        None
+    }
+}
+
+impl Brain for LookaheadBrain {
+    fn get_movement(&mut self, simulation: &Simulation, state: GameState) -> isize {
+        let target = self
+            .landing_x(simulation, state)
+            .unwrap_or(state.ball_pos.0);
+
+        match target.cmp(&state.player_pos.0) {
+            Ordering::Less => -1,
+            Ordering::Equal => 0,
+            Ordering::Greater => 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::assert_clears_the_board;
+
+    #[test]
+    fn lookahead_brain_clears_the_board() {
+        assert_clears_the_board(Box::new(LookaheadBrain::default()), 14096);
+    }
+}
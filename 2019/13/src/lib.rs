@@ -1,3 +1,8 @@
+pub mod arcade;
+pub mod brain;
 pub mod part_a;
 pub mod part_b;
-mod simulation;
+pub mod recording;
+pub mod simulation;
+#[cfg(test)]
+mod test_support;
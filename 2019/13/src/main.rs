@@ -1,12 +1,84 @@
-use std::fs::read_to_string;
+use std::{
+    env,
+    fs::{read_to_string, write},
+    io::stdout,
+};
 
-use aoc_2019_13::{part_a, part_b};
+use aoc_2019_13::{
+    arcade::{Arcade, Control},
+    brain::LookaheadBrain,
+    part_a, part_b,
+    recording::Recording,
+};
 use intcode_computer::program::parse_from_string;
 
+const USAGE: &str = "Options:
+  --headless          play without the terminal front end
+  --human             start with the human in control
+  --lookahead         make the AI play by forking the game
+  --record <file>     save a recording of the game
+  --replay <file>     replay a recording without a player
+  --frames <file>     print the frames of a recording";
+
 fn main() {
     let input = read_to_string("input.txt").expect("cannot read input");
     let program = parse_from_string(&input).expect("cannot parse program");
 
+    let args: Vec<String> = env::args().skip(1).collect();
+    let has_flag = |flag: &str| args.iter().any(|arg| arg == flag);
+    let value_of = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .map(|i| args.get(i + 1).expect(USAGE))
+    };
+
+    if let Some(path) = value_of("--frames") {
+        let recording = read_recording(path);
+        recording
+            .write_frames(stdout().lock())
+            .expect("cannot write frames");
+        return;
+    }
+    if let Some(path) = value_of("--replay") {
+        let recording = read_recording(path);
+        let score = recording
+            .replay(part_b::insert_coins(program))
+            .unwrap_or_else(|e| panic!("cannot replay {}: {}", path, e));
+        println!("Replayed score: {}", score);
+        return;
+    }
+
     println!("Result A: {}", part_a::solve(program.clone()));
-    println!("Result B: {}", part_b::solve(program))
+
+    let control = if has_flag("--human") {
+        Control::Human
+    } else {
+        Control::Ai
+    };
+    let program = part_b::insert_coins(program);
+    let mut arcade = if has_flag("--lookahead") {
+        Arcade::with_brain(program, control, Box::new(LookaheadBrain::default()))
+    } else {
+        Arcade::new(program, control)
+    };
+
+    let record_path = value_of("--record");
+    if record_path.is_some() {
+        arcade.simulation_mut().start_recording();
+    }
+    let score = arcade
+        .run(has_flag("--headless"))
+        .expect("cannot use the terminal");
+
+    if let (Some(path), Some(recording)) = (record_path, arcade.simulation_mut().take_recording()) {
+        write(path, recording.to_string()).expect("cannot write recording");
+    }
+    println!("Result B: {}", score)
+}
+
+fn read_recording(path: &str) -> Recording {
+    read_to_string(path)
+        .expect("cannot read recording")
+        .parse()
+        .unwrap_or_else(|e| panic!("invalid recording {}: {}", path, e))
 }
@@ -1,41 +1,31 @@
-use std::{thread, time::Duration};
+use crate::{
+    arcade::{Arcade, Control},
+    brain::Brain,
+    simulation::{GameState, Position, Simulation},
+};
 
-use intcode_computer::Instruction;
-
-use crate::simulation::{GameState, Simulation};
-const DELAY: Duration = Duration::from_millis(20);
+/// Plays the game with the AI until it halts, without rendering. Returns the final score.
+pub fn solve(program: Vec<isize>) -> isize {
+    Arcade::new(insert_coins(program), Control::Ai)
+        .run(true)
+        .expect("headless game failed")
+}
 
-pub fn solve(mut program: Vec<isize>) -> isize {
+/// Sets the game to free play mode.
+pub fn insert_coins(mut program: Vec<isize>) -> Vec<isize> {
     program[0] = 2;
-    let mut simulation = Simulation::new(program);
-    let mut brain = PlayerBrain::default();
-
-    loop {
-        let (state, instr) = simulation.execute();
-        println!("{}", simulation);
-        println!("Score: {}", simulation.score);
-        if instr == Instruction::Halt {
-            break;
-        }
-        let movement = brain.get_movement(state);
-
-        simulation.send_movement(movement);
-        simulation.computer.exec(&instr);
-        thread::sleep(DELAY);
-    }
-
-    simulation.score
+    program
 }
 
-type Position = (isize, isize);
+/// Predicts the next position of the ball from its last movement.
 #[derive(Default)]
-struct PlayerBrain {
+pub struct PlayerBrain {
     last_ball_position: Option<Position>,
     last_direction: Option<isize>,
 }
 
-impl PlayerBrain {
-    fn get_movement(&mut self, s: GameState) -> isize {
+impl Brain for PlayerBrain {
+    fn get_movement(&mut self, _: &Simulation, s: GameState) -> isize {
         let next_ball_x = self.get_next_ball_x(&s.ball_pos);
         let x_delta = s.player_pos.0 - next_ball_x;
         self.last_ball_position = Some(s.ball_pos);
@@ -58,14 +48,16 @@ impl PlayerBrain {
 
         direction
     }
+}
 
+impl PlayerBrain {
     fn get_next_ball_x(&mut self, ball_pos: &Position) -> isize {
         let last_ball_x = self.last_ball_position.map(|p| p.0).unwrap_or(ball_pos.0);
         let x_delta = ball_pos.0 - last_ball_x;
 
         if x_delta == 0 {
             return match self.last_direction {
-                Some(direction) => ball_pos.0 + direction * -1,
+                Some(direction) => ball_pos.0 - direction,
                 None => ball_pos.0,
             };
         }
@@ -0,0 +1,279 @@
+//! Recordings of arcade games, which can be saved, replayed and exported as text frames.
+//!
+//! A recording is saved as text with one event per line. The updates drawn in a tick are
+//! followed by the joystick movement sent in response, which ends the tick:
+//!
+//! ```text
+//! tile 20 21 4
+//! score 12
+//! input -1
+//! ```
+
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    error::Error,
+    fmt::{self, Display},
+    io::{self, Write},
+    str::FromStr,
+};
+
+use intcode_computer::RunState;
+
+use crate::simulation::{write_tiles, Position, Simulation, TileId};
+
+/// A change drawn by the game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Update {
+    Tile(Position, TileId),
+    Score(isize),
+}
+
+/// What happened between two joystick movements.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tick {
+    pub updates: Vec<Update>,
+    /// `None` for the last tick, after which the game halted or was quit.
+    pub input: Option<isize>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recording {
+    pub ticks: Vec<Tick>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordingError {
+    /// A line of a saved recording is not a valid event. Lines are numbered from 1.
+    InvalidLine { line: usize, content: String },
+    /// The replayed game drew something different than the recorded one in the given tick.
+    Diverged { tick: usize },
+}
+
+impl Error for RecordingError {}
+
+impl Display for RecordingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordingError::InvalidLine { line, content } => {
+                write!(f, "invalid event on line {}: {}", line, content)
+            }
+            RecordingError::Diverged { tick } => {
+                write!(
+                    f,
+                    "replayed game diverged from the recording in tick {}",
+                    tick
+                )
+            }
+        }
+    }
+}
+
+impl Recording {
+    pub(crate) fn push_tick(&mut self, updates: Vec<Update>) {
+        self.ticks.push(Tick {
+            updates,
+            input: None,
+        });
+    }
+
+    pub(crate) fn set_input(&mut self, input: isize) {
+        if let Some(tick) = self.ticks.last_mut() {
+            tick.input = Some(input);
+        }
+    }
+
+    /// The last recorded score.
+    pub fn final_score(&self) -> isize {
+        self.ticks
+            .iter()
+            .rev()
+            .flat_map(|tick| tick.updates.iter().rev())
+            .find_map(|update| match update {
+                Update::Score(score) => Some(*score),
+                Update::Tile(..) => None,
+            })
+            .unwrap_or(0)
+    }
+
+    /// Runs `program` with the recorded joystick movements instead of a player, checking that
+    /// every tick draws the recorded updates. Returns the final score.
+    pub fn replay(&self, program: Vec<isize>) -> Result<isize, RecordingError> {
+        let mut simulation = Simulation::new(program);
+        simulation.start_recording();
+
+        for (index, tick) in self.ticks.iter().enumerate() {
+            let (_, run_state) = simulation.execute();
+            let replayed = simulation.recording().and_then(|r| r.ticks.last());
+            if replayed.map(|t| &t.updates) != Some(&tick.updates) {
+                return Err(RecordingError::Diverged { tick: index });
+            }
+
+            match tick.input {
+                Some(_) if run_state == RunState::Halted => {
+                    return Err(RecordingError::Diverged { tick: index })
+                }
+                Some(input) => simulation.send_movement(input),
+                None => break,
+            }
+        }
+
+        Ok(simulation.score)
+    }
+
+    /// Writes the board after every tick, each preceded by a line with the tick number and the
+    /// score.
+    pub fn write_frames(&self, mut out: impl Write) -> io::Result<()> {
+        let mut tiles = HashMap::new();
+        let mut score = 0;
+
+        for (index, tick) in self.ticks.iter().enumerate() {
+            for update in &tick.updates {
+                match *update {
+                    Update::Tile(position, tile_id) => {
+                        tiles.insert(position, tile_id);
+                    }
+                    Update::Score(s) => score = s,
+                }
+            }
+            if tiles.is_empty() {
+                continue;
+            }
+
+            let mut frame = format!("Tick {}, score {}\n", index, score);
+            write_tiles(&mut frame, &tiles).expect("cannot format frame");
+            writeln!(out, "{}", frame)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Recording {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for tick in &self.ticks {
+            for update in &tick.updates {
+                match update {
+                    Update::Tile((x, y), tile_id) => {
+                        writeln!(f, "tile {} {} {}", x, y, isize::from(*tile_id))?
+                    }
+                    Update::Score(score) => writeln!(f, "score {}", score)?,
+                }
+            }
+            if let Some(input) = tick.input {
+                writeln!(f, "input {}", input)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Recording {
+    type Err = RecordingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut recording = Recording::default();
+        let mut tick = Tick::default();
+
+        for (index, line) in s.lines().enumerate() {
+            let invalid = || RecordingError::InvalidLine {
+                line: index + 1,
+                content: line.to_string(),
+            };
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let numbers = words
+                .iter()
+                .skip(1)
+                .map(|w| w.parse::<isize>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| invalid())?;
+
+            match (words.first(), numbers.as_slice()) {
+                (None, _) => {}
+                (Some(&"tile"), [x, y, tile_id]) => {
+                    let tile_id = TileId::try_from(tile_id).map_err(|_| invalid())?;
+                    tick.updates.push(Update::Tile((*x, *y), tile_id));
+                }
+                (Some(&"score"), [score]) => tick.updates.push(Update::Score(*score)),
+                (Some(&"input"), [input]) => {
+                    tick.input = Some(*input);
+                    recording.ticks.push(std::mem::take(&mut tick));
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        if !tick.updates.is_empty() {
+            recording.ticks.push(tick);
+        }
+
+        Ok(recording)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+
+    use intcode_computer::program::parse_from_string;
+
+    use super::*;
+    use crate::part_b::insert_coins;
+
+    fn program() -> Vec<isize> {
+        insert_coins(parse_from_string(&read_to_string("input.txt").unwrap()).unwrap())
+    }
+
+    /// The first 200 ticks of a game played by `PlayerBrain`, saved with `--record`. Replaying
+    /// it does not run any brain, so it checks the replay against a game recorded before the
+    /// current code.
+    const SAVED_OPENING: &str = "fixtures/player_brain_opening.txt";
+
+    #[test]
+    fn replays_saved_opening() {
+        let text = read_to_string(SAVED_OPENING).unwrap();
+        let recording: Recording = text.parse().unwrap();
+
+        assert_eq!(recording.to_string(), text);
+        assert_eq!(recording.ticks.len(), 200);
+        assert_eq!(recording.final_score(), 1738);
+        assert_eq!(recording.replay(program()), Ok(1738));
+    }
+
+    #[test]
+    fn detects_diverging_replay() {
+        let mut recording = Recording::default();
+        recording.push_tick(vec![Update::Score(7)]);
+        recording.set_input(0);
+
+        assert_eq!(
+            recording.replay(program()),
+            Err(RecordingError::Diverged { tick: 0 })
+        );
+    }
+
+    #[test]
+    fn exports_one_frame_per_tick() {
+        let recording: Recording = "tile 0 0 1\ntile 1 0 4\nscore 3\ninput 1\ntile 1 0 0\n"
+            .parse()
+            .unwrap();
+        let mut frames = Vec::new();
+        recording.write_frames(&mut frames).unwrap();
+
+        assert_eq!(
+            String::from_utf8(frames).unwrap(),
+            "Tick 0, score 3\nWO\n\nTick 1, score 3\nW \n\n"
+        );
+    }
+
+    #[test]
+    fn reports_invalid_lines() {
+        assert_eq!(
+            "score 1\njump 2".parse::<Recording>(),
+            Err(RecordingError::InvalidLine {
+                line: 2,
+                content: "jump 2".to_string()
+            })
+        );
+    }
+}
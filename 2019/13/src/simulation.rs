@@ -1,14 +1,14 @@
 use std::{
-    cell::RefCell,
     collections::HashMap,
     convert::{TryFrom, TryInto},
-    fmt::Display,
-    rc::Rc,
+    fmt::{self, Display},
 };
 
-use intcode_computer::{Computer, Instruction};
+use intcode_computer::{Computer, RunState};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+use crate::recording::{Recording, Update};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TileId {
     Empty,
     Wall,
@@ -48,94 +48,162 @@ impl TryFrom<&isize> for TileId {
     }
 }
 
+impl From<TileId> for isize {
+    fn from(tile_id: TileId) -> Self {
+        match tile_id {
+            TileId::Empty => 0,
+            TileId::Wall => 1,
+            TileId::Block => 2,
+            TileId::HorizontalPaddle => 3,
+            TileId::Ball => 4,
+        }
+    }
+}
+
+pub type Position = (isize, isize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct GameState {
-    pub player_pos: (isize, isize),
-    pub ball_pos: (isize, isize),
+    pub player_pos: Position,
+    pub ball_pos: Position,
 }
 
 pub struct Simulation {
-    pub tiles: HashMap<(isize, isize), TileId>,
-    input: Rc<RefCell<Vec<isize>>>,
+    pub tiles: HashMap<Position, TileId>,
     pub score: isize,
     pub computer: Computer,
+    player_pos: Option<Position>,
+    ball_pos: Option<Position>,
+    /// Tiles drawn during the last `execute`, in drawing order.
+    changed_tiles: Vec<Position>,
+    /// `None` unless recording was started.
+    recording: Option<Recording>,
 }
 
 impl Simulation {
     pub fn new(program: Vec<isize>) -> Self {
-        let input = Rc::new(RefCell::new(Vec::new()));
         Self {
-            input: Rc::clone(&input),
-            computer: Computer::new(program, input),
+            computer: Computer::with_empty_input(program),
             tiles: HashMap::new(),
             score: 0,
+            player_pos: None,
+            ball_pos: None,
+            changed_tiles: Vec::new(),
+            recording: None,
         }
     }
 
-    pub fn execute(&mut self) -> (GameState, Instruction) {
-        let instr = loop {
-            let instr = self.computer.parse_instruction();
-            match instr {
-                intcode_computer::Instruction::ReadInput { .. }
-                | intcode_computer::Instruction::Halt => {
-                    break instr;
-                }
-                _ => {
-                    self.computer.exec(&instr);
-                }
+    /// A copy of the game that can be run ahead independently. The recording is not copied, so
+    /// the fork does not record.
+    pub fn fork(&self) -> Self {
+        Self {
+            computer: self.computer.clone(),
+            tiles: self.tiles.clone(),
+            score: self.score,
+            player_pos: self.player_pos,
+            ball_pos: self.ball_pos,
+            changed_tiles: Vec::new(),
+            recording: None,
+        }
+    }
+
+    /// Runs the game until it needs a joystick movement or halts, applying the drawn tiles.
+    pub fn execute(&mut self) -> (GameState, RunState) {
+        let state = loop {
+            match self.computer.run().expect("game program failed") {
+                RunState::Output(_) => {}
+                state => break state,
             }
         };
 
-        let mut player_pos: Option<(isize, isize)> = None;
-        let mut ball_pos: Option<(isize, isize)> = None;
-        let tiles = &mut self.tiles;
-        let score = &mut self.score;
-        self.computer
-            .output()
+        self.changed_tiles.clear();
+        let output: Vec<isize> = self.computer.output_mut().drain(..).collect();
+        let updates: Vec<Update> = output
             .chunks(3)
-            .for_each(|chunk| match chunk {
-                [-1, 0, s] => {
-                    *score = s.clone();
-                }
+            .map(|chunk| match chunk {
+                [-1, 0, s] => Update::Score(*s),
                 [x, y, tile_id] => {
-                    let tile_id: TileId = tile_id.try_into().expect("invalid tile id");
+                    Update::Tile((*x, *y), tile_id.try_into().expect("invalid tile id"))
+                }
+                _ => panic!("invalid chunk"),
+            })
+            .collect();
+
+        for update in &updates {
+            match *update {
+                Update::Score(score) => self.score = score,
+                Update::Tile(position, tile_id) => {
                     match tile_id {
-                        TileId::Ball => ball_pos = Some((x.clone(), y.clone())),
-                        TileId::HorizontalPaddle => player_pos = Some((x.clone(), y.clone())),
+                        TileId::Ball => self.ball_pos = Some(position),
+                        TileId::HorizontalPaddle => self.player_pos = Some(position),
                         _ => {}
                     }
-                    tiles.insert((x.clone(), y.clone()), tile_id);
+                    self.tiles.insert(position, tile_id);
+                    self.changed_tiles.push(position);
                 }
-                _ => panic!("invalid chunk"),
-            });
+            }
+        }
+        if let Some(recording) = self.recording.as_mut() {
+            recording.push_tick(updates);
+        }
 
         (
             GameState {
-                player_pos: player_pos.expect("player not found"),
-                ball_pos: ball_pos.expect("ball not found"),
+                player_pos: self.player_pos.expect("player not found"),
+                ball_pos: self.ball_pos.expect("ball not found"),
             },
-            instr,
+            state,
         )
     }
 
     pub fn send_movement(&mut self, v: isize) {
-        self.input.borrow_mut().push(v);
+        self.computer.input_mut().push_back(v);
+        if let Some(recording) = self.recording.as_mut() {
+            recording.set_input(v);
+        }
+    }
+
+    /// Records the updates of every following `execute` and every movement sent.
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Recording::default());
+    }
+
+    pub fn recording(&self) -> Option<&Recording> {
+        self.recording.as_ref()
+    }
+
+    pub fn take_recording(&mut self) -> Option<Recording> {
+        self.recording.take()
+    }
+
+    /// Positions of the tiles drawn during the last `execute`, which may repeat.
+    pub fn changed_tiles(&self) -> &[Position] {
+        &self.changed_tiles
     }
 }
 
 impl Display for Simulation {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let min_x = self.tiles.keys().map(|(x, _)| x).min().unwrap().clone();
-        let max_x = self.tiles.keys().map(|(x, _)| x).max().unwrap().clone();
-        let min_y = self.tiles.keys().map(|(_, y)| y).min().unwrap().clone();
-        let max_y = self.tiles.keys().map(|(_, y)| y).max().unwrap().clone();
-
-        for y in min_y..=max_y {
-            for x in min_x..=max_x {
-                write!(f, "{}", self.tiles.get(&(x, y)).unwrap_or(&TileId::Empty))?;
-            }
-            write!(f, "\n")?;
-        }
+        write_tiles(f, &self.tiles)
+    }
+}
+
+/// Draws the smallest rectangle containing all the tiles.
+pub(crate) fn write_tiles(
+    f: &mut impl fmt::Write,
+    tiles: &HashMap<Position, TileId>,
+) -> fmt::Result {
+    let min_x = *tiles.keys().map(|(x, _)| x).min().unwrap();
+    let max_x = *tiles.keys().map(|(x, _)| x).max().unwrap();
+    let min_y = *tiles.keys().map(|(_, y)| y).min().unwrap();
+    let max_y = *tiles.keys().map(|(_, y)| y).max().unwrap();
 
-        Ok(())
+    for y in min_y..=max_y {
+        for x in min_x..=max_x {
+            write!(f, "{}", tiles.get(&(x, y)).unwrap_or(&TileId::Empty))?;
+        }
+        writeln!(f)?;
     }
+
+    Ok(())
 }
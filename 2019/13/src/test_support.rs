@@ -0,0 +1,27 @@
+//! Helpers shared by the tests of several modules.
+
+use std::fs::read_to_string;
+
+use intcode_computer::program::parse_from_string;
+
+use crate::{
+    arcade::{Arcade, Control},
+    brain::Brain,
+    part_b::insert_coins,
+    simulation::TileId,
+};
+
+/// Plays the whole game on the puzzle input headless with `brain`, which must break every block
+/// and end with `expected_score`.
+pub fn assert_clears_the_board(brain: Box<dyn Brain>, expected_score: isize) {
+    let program = parse_from_string(&read_to_string("input.txt").unwrap()).unwrap();
+    // Headless games switch to the AI even when a human should play
+    let mut arcade = Arcade::with_brain(insert_coins(program), Control::Human, brain);
+
+    assert_eq!(arcade.run(true).unwrap(), expected_score);
+    assert!(!arcade
+        .simulation()
+        .tiles
+        .values()
+        .any(|tile| *tile == TileId::Block));
+}
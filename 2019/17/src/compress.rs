@@ -0,0 +1,180 @@
+//! Splitting a sequence into calls of a few reusable functions, as needed to fit a route into
+//! the movement functions of the vacuum robot.
+//!
+//! Sequences and routines are written as comma-separated items, and their written length is
+//! limited. Functions are named `A`, `B`, `C` and so on.
+
+use std::fmt::Display;
+
+/// A main routine calling functions, which together expand to the compressed sequence.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Compression<T> {
+    /// Indices of the called functions, in order.
+    pub routine: Vec<usize>,
+    pub functions: Vec<Vec<T>>,
+}
+
+impl<T: Clone + Display> Compression<T> {
+    /// The main routine written as function names, e.g. `A,B,A`.
+    pub fn routine_text(&self) -> String {
+        let names: Vec<String> = self
+            .routine
+            .iter()
+            .map(|&i| ((b'A' + i as u8) as char).to_string())
+            .collect();
+
+        names.join(",")
+    }
+
+    /// The functions written as comma-separated items.
+    pub fn function_texts(&self) -> Vec<String> {
+        self.functions.iter().map(|f| join(f)).collect()
+    }
+
+    /// The sequence the routine stands for.
+    pub fn expand(&self) -> Vec<T> {
+        self.routine
+            .iter()
+            .flat_map(|&i| self.functions[i].iter().cloned())
+            .collect()
+    }
+}
+
+fn join<T: Display>(items: &[T]) -> String {
+    let items: Vec<String> = items.iter().map(T::to_string).collect();
+
+    items.join(",")
+}
+
+/// Finds a main routine of at most `function_count` functions, such that the routine and every
+/// function are at most `max_len` characters long when written. Returns `None` if there is no
+/// such compression.
+///
+/// Longer functions are tried first.
+pub fn compress<T: PartialEq + Clone + Display>(
+    items: &[T],
+    function_count: usize,
+    max_len: usize,
+) -> Option<Compression<T>> {
+    assert!(function_count <= 26, "functions are named with letters");
+
+    let search = Search {
+        items,
+        lengths: items.iter().map(|item| item.to_string().len()).collect(),
+        function_count,
+        max_len,
+    };
+    let mut routine = Vec::new();
+    let mut functions = Vec::new();
+
+    if search.run(0, &mut routine, &mut functions) {
+        Some(Compression {
+            routine,
+            functions: functions.into_iter().map(<[T]>::to_vec).collect(),
+        })
+    } else {
+        None
+    }
+}
+
+struct Search<'a, T> {
+    items: &'a [T],
+    /// Written length of every item.
+    lengths: Vec<usize>,
+    function_count: usize,
+    max_len: usize,
+}
+
+impl<'a, T: PartialEq> Search<'a, T> {
+    /// Tries to cover the items from `pos` on, extending `routine` and `functions`. They are
+    /// left unchanged if that is not possible.
+    fn run(&self, pos: usize, routine: &mut Vec<usize>, functions: &mut Vec<&'a [T]>) -> bool {
+        if pos == self.items.len() {
+            return true;
+        }
+        // Function names are one character each, plus the separating commas
+        if 2 * (routine.len() + 1) - 1 > self.max_len {
+            return false;
+        }
+
+        let rest = &self.items[pos..];
+        for i in 0..functions.len() {
+            if rest.starts_with(functions[i]) {
+                routine.push(i);
+                if self.run(pos + functions[i].len(), routine, functions) {
+                    return true;
+                }
+                routine.pop();
+            }
+        }
+
+        if functions.len() < self.function_count {
+            let mut written_len = 0;
+            let mut ends = Vec::new();
+            for end in pos + 1..=self.items.len() {
+                written_len += self.lengths[end - 1] + if end > pos + 1 { 1 } else { 0 };
+                if written_len > self.max_len {
+                    break;
+                }
+                ends.push(end);
+            }
+
+            for &end in ends.iter().rev() {
+                functions.push(&self.items[pos..end]);
+                routine.push(functions.len() - 1);
+                if self.run(end, routine, functions) {
+                    return true;
+                }
+                routine.pop();
+                functions.pop();
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compresses_example_route() {
+        let route: Vec<&str> = "R,8,R,8,R,4,R,4,R,8,L,6,L,2,R,4,R,4,R,8,R,8,R,8,L,6,L,2"
+            .split(',')
+            .collect();
+
+        let compression = compress(&route, 3, 20).expect("no compression found");
+
+        assert_eq!(compression.expand(), route);
+        assert!(compression.routine_text().len() <= 20);
+        assert_eq!(compression.functions.len(), 3);
+        for function in compression.function_texts() {
+            assert!(function.len() <= 20, "{} is too long", function);
+        }
+    }
+
+    #[test]
+    fn writes_routine_with_function_names() {
+        let compression = Compression {
+            routine: vec![0, 1, 0, 2],
+            functions: vec![vec![1, 2], vec![3], vec![10]],
+        };
+
+        assert_eq!(compression.routine_text(), "A,B,A,C");
+        assert_eq!(compression.function_texts(), vec!["1,2", "3", "10"]);
+        assert_eq!(compression.expand(), vec![1, 2, 3, 1, 2, 10]);
+    }
+
+    #[test]
+    fn reports_impossible_compression() {
+        // Functions of at most 7 characters hold at most 4 items, so 7 distinct items need 2
+        let items = [1, 2, 3, 4, 5, 6, 7];
+
+        assert_eq!(compress(&items, 1, 7), None);
+        assert_eq!(
+            compress(&items, 2, 7).map(|c| c.routine_text()),
+            Some("A,B".to_string())
+        );
+    }
+}
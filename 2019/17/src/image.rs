@@ -1,4 +1,4 @@
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub struct Point(pub usize, pub usize);
 
 impl std::fmt::Display for Point {
@@ -7,7 +7,7 @@ impl std::fmt::Display for Point {
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum Direction {
     Up,
     Down,
@@ -17,8 +17,8 @@ pub enum Direction {
 
 #[derive(PartialEq, Debug)]
 pub struct Robot {
-    pos: Point,
-    direction: Direction,
+    pub pos: Point,
+    pub direction: Direction,
 }
 
 #[derive(PartialEq, Debug)]
@@ -28,69 +28,66 @@ pub enum Tile {
 }
 
 #[allow(non_snake_case)]
-pub fn parse_1D_image(image: &Vec<isize>) -> (Vec<Vec<Tile>>, Option<Robot>) {
+pub fn parse_1D_image(image: &str) -> (Vec<Vec<Tile>>, Option<Robot>) {
     let mut x: usize = 0;
     let mut y: usize = 0;
 
     let mut image_2d: Vec<Vec<Tile>> = vec![vec![]];
     let mut robot: Option<Robot> = None;
 
-    image
-        .iter()
-        .map(|c| *c as u8 as char)
-        .for_each(|c| match c {
-            '#' => {
-                image_2d[y].push(Tile::Scaffold);
-                x += 1;
-            }
-            '.' => {
-                image_2d[y].push(Tile::Empty);
-                x += 1;
-            }
-            '\n' => {
-                image_2d.push(Vec::new());
-                y += 1;
-                x = 0;
-            }
-            '^' => {
-                image_2d[y].push(Tile::Scaffold);
-                robot = Some(Robot {
-                    pos: Point(x, y),
-                    direction: Direction::Up,
-                });
-                x += 1;
-            }
-            'V' => {
-                image_2d[y].push(Tile::Scaffold);
-                robot = Some(Robot {
-                    pos: Point(x, y),
-                    direction: Direction::Down,
-                });
-                x += 1;
-            }
-            '<' => {
-                image_2d[y].push(Tile::Scaffold);
-                robot = Some(Robot {
-                    pos: Point(x, y),
-                    direction: Direction::Left,
-                });
-                x += 1;
-            }
-            '>' => {
-                image_2d[y].push(Tile::Scaffold);
-                robot = Some(Robot {
-                    pos: Point(x, y),
-                    direction: Direction::Right,
-                });
-                x += 1;
-            }
-            _ => panic!("Unknown tile {} at pos {:?}", c, Point(x, y)),
-        });
+    image.chars().for_each(|c| match c {
+        '#' => {
+            image_2d[y].push(Tile::Scaffold);
+            x += 1;
+        }
+        '.' => {
+            image_2d[y].push(Tile::Empty);
+            x += 1;
+        }
+        '\n' => {
+            image_2d.push(Vec::new());
+            y += 1;
+            x = 0;
+        }
+        '^' => {
+            image_2d[y].push(Tile::Scaffold);
+            robot = Some(Robot {
+                pos: Point(x, y),
+                direction: Direction::Up,
+            });
+            x += 1;
+        }
+        'V' => {
+            image_2d[y].push(Tile::Scaffold);
+            robot = Some(Robot {
+                pos: Point(x, y),
+                direction: Direction::Down,
+            });
+            x += 1;
+        }
+        '<' => {
+            image_2d[y].push(Tile::Scaffold);
+            robot = Some(Robot {
+                pos: Point(x, y),
+                direction: Direction::Left,
+            });
+            x += 1;
+        }
+        '>' => {
+            image_2d[y].push(Tile::Scaffold);
+            robot = Some(Robot {
+                pos: Point(x, y),
+                direction: Direction::Right,
+            });
+            x += 1;
+        }
+        _ => panic!("Unknown tile {} at pos {:?}", c, Point(x, y)),
+    });
 
     (image_2d, robot)
 }
 
-pub fn find_intersections(image: &Vec<Vec<Tile>>) -> Vec<Point> {
+pub fn find_intersections(image: &[Vec<Tile>]) -> Vec<Point> {
     let height = image.len();
     let width = image[0].len();
     let mut neighboring_scaffolds: Vec<Vec<i32>> = vec![vec![0; width]; height];
@@ -132,19 +129,11 @@ pub fn find_intersections(image: &Vec<Vec<Tile>>) -> Vec<Point> {
 
 #[cfg(test)]
 mod tests {
-    use std::iter;
-
     use super::*;
 
-    fn get_image_from_string(str: &str) -> Vec<isize> {
-        str.split("\n")
-            .flat_map(|line| {
-                line.trim()
-                    .as_bytes()
-                    .iter()
-                    .map(|&c| c as isize)
-                    .chain(iter::once('\n' as u8 as isize))
-            })
+    fn get_image_from_string(str: &str) -> String {
+        str.split('\n')
+            .flat_map(|line| line.trim().chars().chain("\n".chars()))
             .collect()
     }
 
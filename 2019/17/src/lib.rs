@@ -1,14 +1,54 @@
+pub mod compress;
 pub mod image;
+pub mod path;
 
+use compress::compress;
 use image::{find_intersections, parse_1D_image, Point};
-use intcode_computer::Computer;
+use intcode_computer::{ascii::AsciiComputer, Computer};
+use path::trace_path;
+
+/// Number of movement functions of the robot.
+const FUNCTION_COUNT: usize = 3;
+/// Maximum length of the main routine and of every movement function, without the newline.
+const MAX_ROUTINE_LEN: usize = 20;
 
 pub fn part_1(program: Vec<isize>) -> usize {
-    let mut computer = Computer::with_empty_input(program);
-    computer.run_till_halt();
+    let mut computer = AsciiComputer::new(Computer::with_empty_input(program));
+    let camera_image = computer.read_until_prompt().expect("camera program failed");
 
-    let (image, _) = parse_1D_image(&computer.output());
+    let (image, _) = parse_1D_image(&camera_image);
     let intersections = find_intersections(&image);
 
     intersections.iter().map(|&Point(x, y)| x * y).sum()
 }
+
+pub fn part_2(mut program: Vec<isize>) -> isize {
+    // Wakes the robot up
+    program[0] = 2;
+    let mut computer = AsciiComputer::new(Computer::with_empty_input(program));
+
+    let camera_image = computer.read_until_prompt().expect("camera program failed");
+    // The image is followed by the prompt for the main routine
+    let image_end = camera_image.find("\n\n").unwrap_or(camera_image.len());
+    let (image, robot) = parse_1D_image(&camera_image[..image_end]);
+    let robot = robot.expect("robot not found");
+
+    let path = trace_path(&image, &robot);
+    let compression =
+        compress(&path, FUNCTION_COUNT, MAX_ROUTINE_LEN).expect("cannot compress the route");
+
+    // The robot asks for every function, even those a shorter compression does not call
+    let mut functions = compression.function_texts();
+    let unused = functions.first().cloned().unwrap_or_default();
+    functions.resize(FUNCTION_COUNT, unused);
+
+    computer.send_line(&compression.routine_text());
+    for function in functions {
+        computer.send_line(&function);
+    }
+    // No continuous video feed
+    computer.send_line("n");
+    computer.read_until_prompt().expect("vacuum program failed");
+
+    computer.result().expect("no dust count reported")
+}
@@ -1,4 +1,4 @@
-use aoc_2019_17::part_1;
+use aoc_2019_17::{part_1, part_2};
 use intcode_computer::program;
 use std::fs::read_to_string;
 
@@ -7,5 +7,6 @@ fn main() {
         program::parse_from_string(&read_to_string("input.txt").expect("Cannot read input file"))
             .unwrap();
 
-    println!("Result A: {}", part_1(program));
+    println!("Result A: {}", part_1(program.clone()));
+    println!("Result B: {}", part_2(program));
 }
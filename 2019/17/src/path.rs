@@ -0,0 +1,159 @@
+use std::fmt::Display;
+
+use crate::image::{Direction, Point, Robot, Tile};
+
+/// A movement command understood by the vacuum robot.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Command {
+    Left,
+    Right,
+    Forward(usize),
+}
+
+impl Display for Command {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Command::Left => write!(f, "L"),
+            Command::Right => write!(f, "R"),
+            Command::Forward(steps) => write!(f, "{}", steps),
+        }
+    }
+}
+
+impl Direction {
+    fn turned_left(self) -> Self {
+        match self {
+            Direction::Up => Direction::Left,
+            Direction::Left => Direction::Down,
+            Direction::Down => Direction::Right,
+            Direction::Right => Direction::Up,
+        }
+    }
+
+    fn turned_right(self) -> Self {
+        match self {
+            Direction::Up => Direction::Right,
+            Direction::Right => Direction::Down,
+            Direction::Down => Direction::Left,
+            Direction::Left => Direction::Up,
+        }
+    }
+}
+
+/// The scaffold tile next to `pos` in the given direction, if there is one.
+fn next_scaffold(image: &[Vec<Tile>], pos: Point, direction: Direction) -> Option<Point> {
+    let Point(x, y) = pos;
+    let next = match direction {
+        Direction::Up => Point(x, y.checked_sub(1)?),
+        Direction::Down => Point(x, y + 1),
+        Direction::Left => Point(x.checked_sub(1)?, y),
+        Direction::Right => Point(x + 1, y),
+    };
+
+    match image.get(next.1).and_then(|row| row.get(next.0)) {
+        Some(Tile::Scaffold) => Some(next),
+        _ => None,
+    }
+}
+
+/// Moves from `pos` in the given direction until the scaffold ends. Returns the number of steps.
+fn go_straight(image: &[Vec<Tile>], pos: &mut Point, direction: Direction) -> usize {
+    let mut steps = 0;
+    while let Some(next) = next_scaffold(image, *pos, direction) {
+        *pos = next;
+        steps += 1;
+    }
+
+    steps
+}
+
+/// Commands that lead the robot over the whole scaffold, going straight through
+/// intersections and turning only at corners, until it reaches the dead end.
+pub fn trace_path(image: &[Vec<Tile>], robot: &Robot) -> Vec<Command> {
+    let mut commands = Vec::new();
+    let mut pos = robot.pos;
+    let mut direction = robot.direction;
+
+    // The robot may start facing along the scaffold, or away from it
+    let behind = direction.turned_left().turned_left();
+    if [direction, direction.turned_left(), direction.turned_right()]
+        .iter()
+        .all(|&d| next_scaffold(image, pos, d).is_none())
+        && next_scaffold(image, pos, behind).is_some()
+    {
+        commands.extend([Command::Left, Command::Left]);
+        direction = behind;
+    }
+    let steps = go_straight(image, &mut pos, direction);
+    if steps > 0 {
+        commands.push(Command::Forward(steps));
+    }
+
+    loop {
+        let (turn, turned) = if next_scaffold(image, pos, direction.turned_left()).is_some() {
+            (Command::Left, direction.turned_left())
+        } else if next_scaffold(image, pos, direction.turned_right()).is_some() {
+            (Command::Right, direction.turned_right())
+        } else {
+            return commands;
+        };
+        commands.push(turn);
+        direction = turned;
+
+        let steps = go_straight(image, &mut pos, direction);
+        commands.push(Command::Forward(steps));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::parse_1D_image;
+
+    fn path_text(image: &str) -> String {
+        let (image, robot) = parse_1D_image(image);
+        let path: Vec<String> = trace_path(&image, &robot.unwrap())
+            .iter()
+            .map(Command::to_string)
+            .collect();
+
+        path.join(",")
+    }
+
+    #[test]
+    fn starts_along_the_scaffold() {
+        assert_eq!(path_text(">###\n...#\n"), "3,R,1");
+    }
+
+    #[test]
+    fn turns_around_at_the_start() {
+        assert_eq!(path_text("<###\n...#\n"), "L,L,3,R,1");
+    }
+
+    #[test]
+    fn traces_scaffold_path() {
+        let image: String = "#######...#####
+            #.....#...#...#
+            #.....#...#...#
+            ......#...#...#
+            ......#...###.#
+            ......#.....#.#
+            ^########...#.#
+            ......#.#...#.#
+            ......#########
+            ........#...#..
+            ....#########..
+            ....#...#......
+            ....#...#......
+            ....#...#......
+            ....#####......"
+            .split('\n')
+            .flat_map(|line| line.trim().chars().chain("\n".chars()))
+            .collect();
+
+        assert_eq!(
+            path_text(&image),
+            "R,8,R,8,R,4,R,4,R,8,L,6,L,2,R,4,R,4,R,8,R,8,R,8,L,6,L,2"
+        );
+    }
+}
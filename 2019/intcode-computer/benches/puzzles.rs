@@ -0,0 +1,189 @@
+//! Runs the 2019 puzzle inputs, with and without the instruction cache, and with the original
+//! string-based decoding of instruction headers as a baseline.
+//!
+//! Each benchmark checks that all variants produce the same result.
+
+use std::collections::HashMap;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use intcode_computer::{program, Computer, RunState};
+
+fn load(day: &str) -> Vec<isize> {
+    let path = format!("{}/../{}/input.txt", env!("CARGO_MANIFEST_DIR"), day);
+    let source = std::fs::read_to_string(&path).expect("cannot read input");
+
+    program::parse_from_string(&source).expect("cannot parse program")
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Variant {
+    Cached,
+    Uncached,
+    /// No cache, and headers decoded by formatting them as strings.
+    Strings,
+}
+
+const VARIANTS: [(&str, Variant); 3] = [
+    ("cached", Variant::Cached),
+    ("uncached", Variant::Uncached),
+    ("strings", Variant::Strings),
+];
+
+fn computer(program: &[isize], input: Vec<isize>, variant: Variant) -> Computer {
+    let mut computer = Computer::new(program.to_vec(), input);
+    computer.set_instruction_cache(matches!(variant, Variant::Cached));
+    computer.set_string_decoding(matches!(variant, Variant::Strings));
+
+    computer
+}
+
+fn run_diagnostics(program: &[isize], system_id: isize, variant: Variant) -> Vec<isize> {
+    let mut computer = computer(program, vec![system_id], variant);
+    computer.run_till_halt().expect("program failed");
+
+    computer.output().clone()
+}
+
+fn permutations(values: Vec<isize>) -> Vec<Vec<isize>> {
+    if values.len() <= 1 {
+        return vec![values];
+    }
+
+    let mut result = Vec::new();
+    for i in 0..values.len() {
+        let mut rest = values.clone();
+        let first = rest.remove(i);
+        for mut permutation in permutations(rest) {
+            permutation.insert(0, first);
+            result.push(permutation);
+        }
+    }
+
+    result
+}
+
+fn max_amplifier_signal(program: &[isize], variant: Variant) -> isize {
+    permutations((5..10).collect())
+        .into_iter()
+        .map(|phases| {
+            let mut amplifiers: Vec<Computer> = phases
+                .iter()
+                .map(|&phase| computer(program, vec![phase], variant))
+                .collect();
+            let mut signal = 0;
+
+            loop {
+                for amplifier in amplifiers.iter_mut() {
+                    amplifier.input_mut().push_back(signal);
+                    match amplifier.run().expect("program failed") {
+                        RunState::Output(value) => signal = value,
+                        _ => return signal,
+                    }
+                }
+            }
+        })
+        .max()
+        .unwrap()
+}
+
+fn painted_panels(program: &[isize], variant: Variant) -> usize {
+    let mut computer = computer(program, vec![], variant);
+    let mut panels: HashMap<(isize, isize), isize> = HashMap::new();
+    let (mut position, mut direction) = ((0, 0), (0, -1));
+
+    loop {
+        let color = panels.get(&position).cloned().unwrap_or(0);
+        computer.input_mut().push_back(color);
+
+        let paint = match computer.run().expect("program failed") {
+            RunState::Output(value) => value,
+            _ => return panels.len(),
+        };
+        let turn = match computer.run().expect("program failed") {
+            RunState::Output(value) => value,
+            _ => return panels.len(),
+        };
+
+        panels.insert(position, paint);
+        direction = if turn == 0 {
+            (direction.1, -direction.0)
+        } else {
+            (-direction.1, direction.0)
+        };
+        position = (position.0 + direction.0, position.1 + direction.1);
+    }
+}
+
+/// Plays the game by always moving the paddle towards the ball. Returns the final score.
+fn play_arcade(program: &[isize], variant: Variant) -> isize {
+    let mut program = program.to_vec();
+    program[0] = 2;
+    let mut computer = computer(&program, vec![], variant);
+    let (mut ball, mut paddle, mut score): (isize, isize, isize) = (0, 0, 0);
+    let mut pending = Vec::with_capacity(3);
+
+    loop {
+        match computer.run().expect("program failed") {
+            RunState::Output(value) => pending.push(value),
+            RunState::AwaitingInput => computer.input_mut().push_back((ball - paddle).signum()),
+            RunState::Halted => return score,
+        }
+
+        if let [x, y, tile] = pending[..] {
+            match (x, y, tile) {
+                (-1, 0, value) => score = value,
+                (x, _, 3) => paddle = x,
+                (x, _, 4) => ball = x,
+                _ => {}
+            }
+            pending.clear();
+        }
+    }
+}
+
+fn bench_variants<T, F>(c: &mut Criterion, name: &str, f: F)
+where
+    T: PartialEq + std::fmt::Debug,
+    F: Fn(Variant) -> T,
+{
+    let expected = f(Variant::Cached);
+    for &(variant_name, variant) in &VARIANTS[1..] {
+        assert_eq!(
+            f(variant),
+            expected,
+            "{}: {} changed the result",
+            name,
+            variant_name
+        );
+    }
+
+    let mut group = c.benchmark_group(name);
+    for &(variant_name, variant) in &VARIANTS {
+        group.bench_function(variant_name, |b| b.iter(|| f(variant)));
+    }
+    group.finish();
+}
+
+fn puzzles(c: &mut Criterion) {
+    let day05 = load("05");
+    bench_variants(c, "day05", |variant| run_diagnostics(&day05, 5, variant));
+
+    let day07 = load("07");
+    bench_variants(c, "day07", |variant| max_amplifier_signal(&day07, variant));
+
+    let day09 = load("09");
+    bench_variants(c, "day09", |variant| run_diagnostics(&day09, 2, variant));
+
+    let day11 = load("11");
+    bench_variants(c, "day11", |variant| painted_panels(&day11, variant));
+
+    let day13 = load("13");
+    bench_variants(c, "day13", |variant| play_arcade(&day13, variant));
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = puzzles
+}
+criterion_main!(benches);
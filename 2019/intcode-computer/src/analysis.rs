@@ -0,0 +1,339 @@
+//! Static analysis of the control flow of programs.
+//!
+//! Like the disassembler, the analysis only follows jumps with immediate destinations. Jumps
+//! with other destinations are reported as indirect, as they may lead anywhere.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    disassembler::{disassemble_instruction, find_reachable_instructions, immediate_jump_target},
+    instruction::{ArgMode, InstructionHeader, Opcode},
+};
+
+/// A sequence of instructions that is always executed from start to end.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub start: usize,
+    /// Address right after the last instruction of the block.
+    pub end: usize,
+    /// Addresses of the instructions in the block.
+    pub instructions: Vec<usize>,
+    /// Start addresses of the blocks that may be executed next.
+    pub successors: Vec<usize>,
+    /// Whether the block ends with a jump whose destination is not known statically.
+    pub indirect_jump: bool,
+}
+
+/// A write to a cell that belongs to an instruction, which changes the code of the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelfModifyingWrite {
+    /// Address of the writing instruction.
+    pub ip: usize,
+    pub address: usize,
+    /// Address of the instruction that is modified.
+    pub target: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analysis {
+    pub blocks: BTreeMap<usize, BasicBlock>,
+    /// Addresses of jumps whose destination is not an immediate value.
+    pub indirect_jumps: Vec<usize>,
+    /// Writes with position mode addresses that land inside reachable code. Writes with relative
+    /// mode addresses cannot be resolved statically and are not included.
+    pub self_modifying_writes: Vec<SelfModifyingWrite>,
+    /// Addresses of `in` instructions.
+    pub inputs: Vec<usize>,
+    /// Addresses of `out` instructions.
+    pub outputs: Vec<usize>,
+}
+
+pub fn analyze(program: &[isize]) -> Analysis {
+    let instructions = find_reachable_instructions(program);
+    let leaders = find_leaders(program, &instructions);
+
+    let mut analysis = Analysis {
+        blocks: BTreeMap::new(),
+        indirect_jumps: Vec::new(),
+        self_modifying_writes: Vec::new(),
+        inputs: Vec::new(),
+        outputs: Vec::new(),
+    };
+
+    // Owning instruction of every cell of reachable code
+    let mut code_cells = BTreeMap::new();
+    for (&addr, header) in &instructions {
+        for cell in addr..addr + header.opcode.instruction_len() {
+            code_cells.entry(cell).or_insert(addr);
+        }
+    }
+
+    let mut current: Option<BasicBlock> = None;
+    for (&addr, header) in &instructions {
+        let continues_block = current.as_ref().is_some_and(|b| b.end == addr);
+        if !continues_block || leaders.contains(&addr) {
+            if let Some(mut block) = current.take() {
+                if block.end == addr {
+                    block.successors.push(addr);
+                }
+                analysis.blocks.insert(block.start, block);
+            }
+            current = Some(BasicBlock {
+                start: addr,
+                end: addr,
+                instructions: Vec::new(),
+                successors: Vec::new(),
+                indirect_jump: false,
+            });
+        }
+
+        let block = current.as_mut().unwrap();
+        block.instructions.push(addr);
+        block.end = addr + header.opcode.instruction_len();
+
+        match header.opcode {
+            Opcode::ReadInput => analysis.inputs.push(addr),
+            Opcode::WriteOutput => analysis.outputs.push(addr),
+            _ => {}
+        }
+
+        if let Some(address) = written_address(program, addr, header) {
+            if let Some(&target) = code_cells.get(&address) {
+                analysis.self_modifying_writes.push(SelfModifyingWrite {
+                    ip: addr,
+                    address,
+                    target,
+                });
+            }
+        }
+
+        if ends_block(header) {
+            let mut block = current.take().unwrap();
+            block.successors = jump_successors(program, addr, header, &instructions);
+            if is_jump(header) && immediate_jump_target(program, addr, header).is_none() {
+                block.indirect_jump = true;
+                analysis.indirect_jumps.push(addr);
+            }
+            analysis.blocks.insert(block.start, block);
+        }
+    }
+    if let Some(block) = current {
+        analysis.blocks.insert(block.start, block);
+    }
+
+    analysis
+}
+
+impl Analysis {
+    /// Formats the control-flow graph in the Graphviz DOT language. Blocks ending with an
+    /// indirect jump get an edge to a separate `indirect` node.
+    pub fn to_dot(&self, program: &[isize]) -> String {
+        let modified: BTreeSet<usize> = self
+            .self_modifying_writes
+            .iter()
+            .map(|w| w.target)
+            .collect();
+
+        let mut dot =
+            String::from("digraph cfg {\n    node [shape=box, fontname=\"monospace\"];\n");
+        for block in self.blocks.values() {
+            let label: String = block
+                .instructions
+                .iter()
+                .map(|&addr| {
+                    let text = disassemble_instruction(&program[addr..]).unwrap_or_default();
+                    let marker = if modified.contains(&addr) {
+                        " (modified)"
+                    } else {
+                        ""
+                    };
+                    format!("{}: {}{}\\l", addr, text, marker)
+                })
+                .collect();
+            dot.push_str(&format!("    b{} [label=\"{}\"];\n", block.start, label));
+        }
+
+        for block in self.blocks.values() {
+            for successor in &block.successors {
+                dot.push_str(&format!("    b{} -> b{};\n", block.start, successor));
+            }
+        }
+
+        if !self.indirect_jumps.is_empty() {
+            dot.push_str("    indirect [shape=diamond];\n");
+            for block in self.blocks.values().filter(|b| b.indirect_jump) {
+                dot.push_str(&format!(
+                    "    b{} -> indirect [style=dashed];\n",
+                    block.start
+                ));
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Addresses where a new basic block starts: the entry point, jump destinations and
+/// instructions following jumps.
+fn find_leaders(
+    program: &[isize],
+    instructions: &BTreeMap<usize, InstructionHeader>,
+) -> BTreeSet<usize> {
+    let mut leaders = BTreeSet::new();
+    leaders.insert(0);
+
+    for (&addr, header) in instructions {
+        if let Some(target) = immediate_jump_target(program, addr, header) {
+            leaders.insert(target);
+        }
+        if ends_block(header) {
+            leaders.insert(addr + header.opcode.instruction_len());
+        }
+    }
+
+    leaders
+}
+
+fn is_jump(header: &InstructionHeader) -> bool {
+    matches!(header.opcode, Opcode::JumpIfTrue | Opcode::JumpIfFalse)
+}
+
+fn ends_block(header: &InstructionHeader) -> bool {
+    is_jump(header) || header.opcode == Opcode::Halt
+}
+
+/// Blocks that may run after the jump or halt at `addr`. Jumps with an immediate condition are
+/// either always or never taken.
+fn jump_successors(
+    program: &[isize],
+    addr: usize,
+    header: &InstructionHeader,
+    instructions: &BTreeMap<usize, InstructionHeader>,
+) -> Vec<usize> {
+    if header.opcode == Opcode::Halt {
+        return Vec::new();
+    }
+
+    let condition = match header.mode(1) {
+        ArgMode::Immediate => Some(program[addr + 1] != 0),
+        _ => None,
+    };
+    let taken_when = header.opcode == Opcode::JumpIfTrue;
+    let next = addr + header.opcode.instruction_len();
+
+    let mut successors = Vec::new();
+    if condition != Some(!taken_when) {
+        successors.extend(
+            immediate_jump_target(program, addr, header)
+                .filter(|target| instructions.contains_key(target)),
+        );
+    }
+    if condition != Some(taken_when) && instructions.contains_key(&next) {
+        successors.push(next);
+    }
+
+    successors
+}
+
+/// The address written by the instruction, if it is known statically.
+fn written_address(program: &[isize], addr: usize, header: &InstructionHeader) -> Option<usize> {
+    let arg_index = match header.opcode {
+        Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => 3,
+        Opcode::ReadInput => 1,
+        _ => return None,
+    };
+
+    let value = program[addr + arg_index];
+    if header.mode(arg_index) == ArgMode::Position && value >= 0 {
+        Some(value as usize)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+
+    const LOOP: &str = "
+                in [n]
+        loop:   jf [n], end
+                out [n]
+                add [n], #-1, [n]
+                jt #1, loop
+        end:    add #1, #0, [patch]
+        patch:  out #5
+                jt [n], rb+0
+                hlt
+        n:      .data 0
+    ";
+
+    #[test]
+    fn splits_program_into_blocks() {
+        let program = assemble(LOOP).unwrap();
+        let analysis = analyze(&program);
+
+        let blocks: Vec<(usize, usize, Vec<usize>)> = analysis
+            .blocks
+            .values()
+            .map(|b| (b.start, b.end, b.successors.clone()))
+            .collect();
+        assert_eq!(
+            blocks,
+            vec![
+                (0, 2, vec![2]),
+                (2, 5, vec![14, 5]),
+                (5, 14, vec![2]),
+                (14, 23, vec![23]),
+                (23, 24, vec![]),
+            ]
+        );
+        assert!(analysis.blocks[&14].indirect_jump);
+    }
+
+    #[test]
+    fn finds_interesting_sites() {
+        let program = assemble(LOOP).unwrap();
+        let analysis = analyze(&program);
+
+        assert_eq!(analysis.inputs, vec![0]);
+        assert_eq!(analysis.outputs, vec![5, 18]);
+        assert_eq!(analysis.indirect_jumps, vec![20]);
+        assert_eq!(
+            analysis.self_modifying_writes,
+            vec![SelfModifyingWrite {
+                ip: 14,
+                address: 18,
+                target: 18
+            }]
+        );
+    }
+
+    #[test]
+    fn decodes_non_canonical_headers() {
+        let analysis = analyze(&[11104, 5, 104, 6, 99]);
+
+        assert_eq!(analysis.outputs, vec![0, 2]);
+        assert_eq!(analysis.blocks[&0].instructions, vec![0, 2, 4]);
+    }
+
+    #[test]
+    fn exports_dot() {
+        let program = assemble("in [5]\njt [5], #0\nhlt").unwrap();
+        let dot = analyze(&program).to_dot(&program);
+
+        assert_eq!(
+            dot,
+            "digraph cfg {
+    node [shape=box, fontname=\"monospace\"];
+    b0 [label=\"0: in [5]\\l2: jt [5], #0\\l\"];
+    b5 [label=\"5: hlt (modified)\\l\"];
+    b0 -> b0;
+    b0 -> b5;
+}
+"
+        );
+    }
+}
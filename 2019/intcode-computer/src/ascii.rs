@@ -0,0 +1,222 @@
+//! Text-based communication with programs using ASCII codes as input and output values.
+
+use std::{
+    error::Error,
+    fmt::Display,
+    io::{self, BufRead, Write},
+};
+
+use crate::{Computer, IntcodeError, RunState};
+
+/// Wraps a `Computer` whose input and output values are ASCII characters.
+///
+/// Output values that are not ASCII codes, like a large number output after the text, are not
+/// part of the text. The last one is available as `result`.
+pub struct AsciiComputer {
+    computer: Computer,
+    /// Text that was output but not returned yet.
+    pending: String,
+    result: Option<isize>,
+    halted: bool,
+}
+
+impl AsciiComputer {
+    pub fn new(computer: Computer) -> Self {
+        Self {
+            computer,
+            pending: String::new(),
+            result: None,
+            halted: false,
+        }
+    }
+
+    pub fn computer(&self) -> &Computer {
+        &self.computer
+    }
+
+    pub fn computer_mut(&mut self) -> &mut Computer {
+        &mut self.computer
+    }
+
+    pub fn into_inner(self) -> Computer {
+        self.computer
+    }
+
+    /// The last output value that is not an ASCII code.
+    pub fn result(&self) -> Option<isize> {
+        self.result
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Sends the text followed by a newline.
+    pub fn send_line(&mut self, line: &str) {
+        let input = self.computer.input_mut();
+        input.extend(line.bytes().map(isize::from));
+        input.push_back(b'\n' as isize);
+    }
+
+    /// Runs the program until it outputs a full line, which is returned without the newline.
+    ///
+    /// If the program stops before finishing the line, the text output so far is returned.
+    /// Returns `None` if it stops without outputting any text.
+    pub fn read_line(&mut self) -> Result<Option<String>, IntcodeError> {
+        loop {
+            if let Some(end) = self.pending.find('\n') {
+                let line = self.pending[..end].to_string();
+                self.pending.drain(..=end);
+
+                return Ok(Some(line));
+            }
+
+            if !self.run_once()? {
+                if self.pending.is_empty() {
+                    return Ok(None);
+                }
+
+                return Ok(Some(std::mem::take(&mut self.pending)));
+            }
+        }
+    }
+
+    /// Runs the program until it needs input or halts. Returns the text output in the meantime.
+    pub fn read_until_prompt(&mut self) -> Result<String, IntcodeError> {
+        while self.run_once()? {}
+
+        Ok(std::mem::take(&mut self.pending))
+    }
+
+    /// Lets the user talk to the program. Text output by the program is written to `output` and
+    /// lines read from `input` are sent to the program, until it halts or `input` ends.
+    pub fn interact(
+        &mut self,
+        mut input: impl BufRead,
+        mut output: impl Write,
+    ) -> Result<(), InteractError> {
+        loop {
+            let text = self.read_until_prompt()?;
+            write!(output, "{}", text)?;
+
+            if self.halted {
+                if let Some(result) = self.result {
+                    writeln!(output, "{}", result)?;
+                }
+                return Ok(());
+            }
+            output.flush()?;
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            self.send_line(line.trim_end_matches(['\r', '\n']));
+        }
+    }
+
+    /// Runs until the next output. Returns `false` if the program stopped instead.
+    fn run_once(&mut self) -> Result<bool, IntcodeError> {
+        match self.computer.run()? {
+            RunState::Output(value) => {
+                match value {
+                    0..=127 => self.pending.push(value as u8 as char),
+                    _ => self.result = Some(value),
+                }
+
+                Ok(true)
+            }
+            RunState::AwaitingInput => Ok(false),
+            RunState::Halted => {
+                self.halted = true;
+
+                Ok(false)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum InteractError {
+    Io(io::Error),
+    Intcode(IntcodeError),
+}
+
+impl Error for InteractError {}
+
+impl Display for InteractError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InteractError::Io(e) => write!(f, "{}", e),
+            InteractError::Intcode(e) => write!(f, "program failed: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for InteractError {
+    fn from(e: io::Error) -> Self {
+        InteractError::Io(e)
+    }
+}
+
+impl From<IntcodeError> for InteractError {
+    fn from(e: IntcodeError) -> Self {
+        InteractError::Intcode(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+
+    // Asks for a name, greets the user and outputs the length of the name plus 1000
+    const GREETER: &str = "
+                out #62 ; >
+                out #32
+        read:   in [char]
+                eq [char], #10, [done]
+                jt [done], greet
+                add [len], #1, [len]
+                jt #1, read
+        greet:  out #72 ; H
+                out #105 ; i
+                out #10
+                out #33 ; !
+                add [len], #1000, [len]
+                out [len]
+                hlt
+        char:   .data 0
+        done:   .data 0
+        len:    .data 0
+    ";
+
+    fn greeter() -> AsciiComputer {
+        AsciiComputer::new(Computer::with_empty_input(assemble(GREETER).unwrap()))
+    }
+
+    #[test]
+    fn reads_text_and_result() {
+        let mut computer = greeter();
+
+        assert_eq!(computer.read_until_prompt(), Ok("> ".to_string()));
+        assert!(!computer.is_halted());
+
+        computer.send_line("Ann");
+        assert_eq!(computer.read_line(), Ok(Some("Hi".to_string())));
+        assert_eq!(computer.read_line(), Ok(Some("!".to_string())));
+        assert_eq!(computer.read_line(), Ok(None));
+        assert!(computer.is_halted());
+        assert_eq!(computer.result(), Some(1003));
+    }
+
+    #[test]
+    fn interacts_with_user() {
+        let mut computer = greeter();
+        let mut output = Vec::new();
+
+        computer.interact(&b"Bob\n"[..], &mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "> Hi\n!1003\n");
+    }
+}
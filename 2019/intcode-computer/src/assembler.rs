@@ -0,0 +1,514 @@
+//! Assembler for a textual Intcode assembly language.
+//!
+//! ```text
+//! ; comments start with a semicolon
+//! start:  in [12]            ; position mode
+//!         jf [12], end       ; a bare label is an immediate address
+//!         add #-1, rb+3, [12]
+//!         jt #1, start
+//! end:    hlt
+//!         .data 0, 1, end
+//! ```
+//!
+//! Operands use `[x]` for position mode, `#x` for immediate mode and `rb+n`/`rb-n` for relative
+//! mode. Lines may start with an address followed by a colon, like the ones in the listings
+//! produced by the disassembler. The address is checked against the address of the line.
+
+use std::{collections::HashMap, error::Error, fmt::Display};
+
+use crate::instruction::{ArgMode, InstructionHeader, Opcode};
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct AssembleError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl Error for AssembleError {}
+
+impl Display for AssembleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+pub fn assemble(source: &str) -> Result<Vec<isize>, Vec<AssembleError>> {
+    let mut errors = Vec::new();
+    let mut labels: HashMap<String, usize> = HashMap::new();
+    let mut statements = Vec::new();
+    let mut addr = 0;
+
+    for (line_index, line) in source.lines().enumerate() {
+        let mut parser = LineParser {
+            line,
+            line_number: line_index + 1,
+            pos: 0,
+        };
+
+        let result = parser.parse(addr, |name, column| {
+            if labels.insert(name.to_owned(), addr).is_some() {
+                Err(parser_error(
+                    line_index + 1,
+                    column,
+                    format!("duplicate label {}", name),
+                ))
+            } else {
+                Ok(())
+            }
+        });
+
+        match result {
+            Ok(Some(statement)) => {
+                addr += statement.len();
+                statements.push(statement);
+            }
+            Ok(None) => {}
+            Err(e) => errors.push(e),
+        }
+    }
+
+    let mut program = Vec::with_capacity(addr);
+    for statement in statements {
+        let values: Vec<&Value> = match &statement.kind {
+            StatementKind::Instruction { header, operands } => {
+                program.push(header.value());
+                operands.iter().map(|o| &o.value).collect()
+            }
+            StatementKind::Data(values) => values.iter().collect(),
+        };
+
+        for value in values {
+            match value.resolve(&labels) {
+                Some(v) => program.push(v),
+                None => errors.push(parser_error(
+                    statement.line_number,
+                    value.column,
+                    format!("undefined label {}", value.text),
+                )),
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        // Undefined labels are only found after parsing every line
+        errors.sort_by_key(|e| (e.line, e.column));
+        return Err(errors);
+    }
+
+    Ok(program)
+}
+
+fn parser_error(line: usize, column: usize, message: String) -> AssembleError {
+    AssembleError {
+        line,
+        column,
+        message,
+    }
+}
+
+struct Statement {
+    line_number: usize,
+    kind: StatementKind,
+}
+
+impl Statement {
+    fn len(&self) -> usize {
+        match &self.kind {
+            StatementKind::Instruction { operands, .. } => operands.len() + 1,
+            StatementKind::Data(values) => values.len(),
+        }
+    }
+}
+
+enum StatementKind {
+    Instruction {
+        header: InstructionHeader,
+        operands: Vec<Operand>,
+    },
+    Data(Vec<Value>),
+}
+
+struct Operand {
+    mode: ArgMode,
+    value: Value,
+}
+
+/// A number or a label, resolved after all labels are known.
+struct Value {
+    text: String,
+    column: usize,
+}
+
+impl Value {
+    fn resolve(&self, labels: &HashMap<String, usize>) -> Option<isize> {
+        self.text
+            .parse()
+            .ok()
+            .or_else(|| labels.get(&self.text).map(|&addr| addr as isize))
+    }
+}
+
+struct LineParser<'a> {
+    line: &'a str,
+    line_number: usize,
+    /// Byte offset of the next character to parse.
+    pos: usize,
+}
+
+impl<'a> LineParser<'a> {
+    fn parse(
+        &mut self,
+        addr: usize,
+        mut define_label: impl FnMut(&str, usize) -> Result<(), AssembleError>,
+    ) -> Result<Option<Statement>, AssembleError> {
+        if let Some(comment_start) = self.line.find(';') {
+            self.line = &self.line[..comment_start];
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.rest().is_empty() {
+                return Ok(None);
+            }
+
+            let column = self.column();
+            let word = self.take_while(|c| !c.is_whitespace() && c != ':' && c != ',');
+            if word.is_empty() {
+                return Err(self.error_at(column, "expected label or mnemonic".into()));
+            }
+
+            if !self.rest().starts_with(':') {
+                return self.parse_statement(word, column).map(Some);
+            }
+            self.pos += 1;
+
+            if let Ok(expected_addr) = word.parse::<usize>() {
+                if expected_addr != addr {
+                    return Err(self.error_at(
+                        column,
+                        format!(
+                            "expected address {}, but line is at {}",
+                            expected_addr, addr
+                        ),
+                    ));
+                }
+            } else if is_identifier(word) {
+                define_label(word, column)?;
+            } else {
+                return Err(self.error_at(column, format!("invalid label {}", word)));
+            }
+        }
+    }
+
+    fn parse_statement(
+        &mut self,
+        mnemonic: &str,
+        column: usize,
+    ) -> Result<Statement, AssembleError> {
+        let args = self.parse_args()?;
+
+        let kind = if mnemonic == ".data" {
+            if args.is_empty() {
+                return Err(self.error_at(column, ".data needs at least one value".into()));
+            }
+
+            StatementKind::Data(
+                args.into_iter()
+                    .map(|(text, column)| self.parse_value(text, column))
+                    .collect::<Result<_, _>>()?,
+            )
+        } else {
+            let opcode = Opcode::from_mnemonic(mnemonic)
+                .ok_or_else(|| self.error_at(column, format!("unknown mnemonic {}", mnemonic)))?;
+
+            if args.len() != opcode.args_count() {
+                return Err(self.error_at(
+                    column,
+                    format!(
+                        "{} takes {} operands, got {}",
+                        mnemonic,
+                        opcode.args_count(),
+                        args.len()
+                    ),
+                ));
+            }
+
+            let operands = args
+                .into_iter()
+                .map(|(text, column)| self.parse_operand(text, column))
+                .collect::<Result<Vec<_>, _>>()?;
+            let modes: Vec<ArgMode> = operands.iter().map(|o| o.mode).collect();
+
+            StatementKind::Instruction {
+                header: InstructionHeader::new(opcode, &modes),
+                operands,
+            }
+        };
+
+        Ok(Statement {
+            line_number: self.line_number,
+            kind,
+        })
+    }
+
+    /// Splits the rest of the line into comma-separated arguments with their columns.
+    fn parse_args(&mut self) -> Result<Vec<(&'a str, usize)>, AssembleError> {
+        let mut args = Vec::new();
+
+        self.skip_whitespace();
+        if self.rest().is_empty() {
+            return Ok(args);
+        }
+
+        loop {
+            self.skip_whitespace();
+            let column = self.column();
+            let arg = self.take_while(|c| c != ',').trim_end();
+            if arg.is_empty() {
+                return Err(self.error_at(column, "expected operand".into()));
+            }
+            args.push((arg, column));
+
+            if self.rest().is_empty() {
+                return Ok(args);
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn parse_operand(&self, text: &str, column: usize) -> Result<Operand, AssembleError> {
+        let (mode, value) = if let Some(value) = text.strip_prefix('#') {
+            (ArgMode::Immediate, self.parse_value(value, column + 1)?)
+        } else if let Some(inner) = text.strip_prefix('[') {
+            let inner = inner
+                .strip_suffix(']')
+                .ok_or_else(|| self.error_at(column, format!("missing ] in {}", text)))?;
+            (
+                ArgMode::Position,
+                self.parse_value(inner.trim(), column + 1)?,
+            )
+        } else if let Some(offset) = text.strip_prefix("rb") {
+            let offset = offset.replace(' ', "");
+            // A single sign followed by digits, so that `rb+-3` is not taken for `rb-3`
+            let digits = offset.get(1..).unwrap_or_default();
+            let offset = if offset.is_empty() {
+                0
+            } else {
+                offset
+                    .strip_prefix('+')
+                    .unwrap_or(&offset)
+                    .parse::<isize>()
+                    .ok()
+                    .filter(|_| offset.starts_with(['+', '-']))
+                    .filter(|_| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+                    .ok_or_else(|| {
+                        self.error_at(column, format!("invalid relative offset in {}", text))
+                    })?
+            };
+            (
+                ArgMode::Relative,
+                Value {
+                    text: offset.to_string(),
+                    column,
+                },
+            )
+        } else if is_identifier(text) {
+            (ArgMode::Immediate, self.parse_value(text, column)?)
+        } else {
+            return Err(self.error_at(
+                column,
+                format!(
+                    "expected #value, [address], rb+offset or label, got {}",
+                    text
+                ),
+            ));
+        };
+
+        Ok(Operand { mode, value })
+    }
+
+    fn parse_value(&self, text: &str, column: usize) -> Result<Value, AssembleError> {
+        if text.parse::<isize>().is_err() && !is_identifier(text) {
+            return Err(self.error_at(column, format!("invalid value {}", text)));
+        }
+
+        Ok(Value {
+            text: text.to_owned(),
+            column,
+        })
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.line[self.pos..]
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(|c| !predicate(c)).unwrap_or(rest.len());
+        self.pos += len;
+
+        &rest[..len]
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    /// 1-based column of the next character to parse.
+    fn column(&self) -> usize {
+        self.line[..self.pos].chars().count() + 1
+    }
+
+    fn error_at(&self, column: usize, message: String) -> AssembleError {
+        parser_error(self.line_number, column, message)
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    let starts_correctly = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+
+    starts_correctly && chars.all(|c| c.is_ascii_alphanumeric() || c == '_') && s != "rb"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassembler::disassemble;
+
+    #[test]
+    fn assembles_all_addressing_modes() {
+        let source = "
+            ; echo the input doubled
+            start:  in [9]
+                    mul [9], #2, rb+0
+                    out rb-0
+                    hlt
+            .data 0
+        ";
+
+        assert_eq!(
+            assemble(source),
+            Ok(vec![3, 9, 21002, 9, 2, 0, 204, 0, 99, 0])
+        );
+    }
+
+    #[test]
+    fn resolves_labels() {
+        let source = "
+            loop:
+                jt #1, end ; forward reference
+                jf [counter], loop
+            end: hlt
+            counter: .data 5, end
+        ";
+
+        assert_eq!(assemble(source), Ok(vec![1105, 1, 6, 1006, 7, 0, 99, 5, 6]));
+    }
+
+    #[test]
+    fn checks_addresses() {
+        assert_eq!(
+            assemble("0: in [4]\n2: out [4]\n4: hlt"),
+            Ok(vec![3, 4, 4, 4, 99])
+        );
+
+        assert_eq!(
+            assemble("0: in [4]\n3: out [4]"),
+            Err(vec![AssembleError {
+                line: 2,
+                column: 1,
+                message: "expected address 3, but line is at 2".into()
+            }])
+        );
+    }
+
+    #[test]
+    fn reports_errors_with_positions() {
+        let source = "in [1]
+  foo #1
+add #1, #2
+out [x]
+jt #1, nowhere
+  out #1 ,
+eq #1, 2, [3]
+rb: hlt
+in [1";
+        let errors = assemble(source).unwrap_err();
+        let positions: Vec<(usize, usize, &str)> = errors
+            .iter()
+            .map(|e| (e.line, e.column, e.message.as_str()))
+            .collect();
+
+        assert_eq!(
+            positions,
+            vec![
+                (2, 3, "unknown mnemonic foo"),
+                (3, 1, "add takes 3 operands, got 2"),
+                (4, 6, "undefined label x"),
+                (5, 8, "undefined label nowhere"),
+                (6, 11, "expected operand"),
+                (
+                    7,
+                    8,
+                    "expected #value, [address], rb+offset or label, got 2"
+                ),
+                (8, 1, "invalid label rb"),
+                (9, 4, "missing ] in [1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_doubly_signed_relative_offsets() {
+        for source in ["out rb+-3", "out rb++3", "out rb-+3", "out rb+"] {
+            let errors = assemble(source).unwrap_err();
+            assert_eq!(
+                errors[0].message,
+                format!("invalid relative offset in {}", &source[4..]),
+                "{}",
+                source
+            );
+        }
+
+        assert_eq!(assemble("out rb-3"), Ok(vec![204, -3]));
+        assert_eq!(
+            assemble(&format!("out rb-{}", isize::MIN.unsigned_abs())),
+            Ok(vec![204, isize::MIN])
+        );
+    }
+
+    fn assert_round_trip(program: Vec<isize>) {
+        let listing = disassemble(&program);
+
+        assert_eq!(
+            assemble(&listing),
+            Ok(program),
+            "listing does not round-trip:\n{}",
+            listing
+        );
+    }
+
+    #[test]
+    fn round_trips_computer_examples() {
+        assert_round_trip(vec![1002, 4, 3, 4, 33]);
+        assert_round_trip(vec![
+            3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9,
+        ]);
+        assert_round_trip(vec![3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1]);
+        assert_round_trip(vec![
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99,
+        ]);
+        assert_round_trip(vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ]);
+        assert_round_trip(vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0]);
+        assert_round_trip(vec![104, 1125899906842624, 99]);
+        assert_round_trip(vec![10099, 30001, -5]);
+    }
+}
@@ -0,0 +1,31 @@
+use std::{
+    env,
+    fs::read_to_string,
+    io::{stdin, stdout},
+    process,
+};
+
+use intcode_computer::{ascii::AsciiComputer, program, Computer};
+
+fn main() {
+    let path = env::args().nth(1).unwrap_or_else(|| {
+        eprintln!("Usage: intcode-ascii <program file>");
+        process::exit(2);
+    });
+    let source = read_to_string(&path).unwrap_or_else(|e| {
+        eprintln!("cannot read {}: {}", path, e);
+        process::exit(2);
+    });
+    let program = program::parse_from_string(&source).unwrap_or_else(|errors| {
+        for e in errors {
+            eprintln!("{}", e);
+        }
+        process::exit(2);
+    });
+
+    let mut computer = AsciiComputer::new(Computer::with_empty_input(program));
+    if let Err(e) = computer.interact(stdin().lock(), stdout()) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,195 @@
+use std::{
+    env,
+    fs::read_to_string,
+    io::{stdin, stdout, BufRead, Write},
+    process,
+};
+
+use intcode_computer::{
+    debugger::{Breakpoint, Debugger, StopReason},
+    disassembler::disassemble_instruction,
+    program, Computer, Opcode,
+};
+
+const HELP: &str = "Commands:
+  s, step [n]              execute n instructions (default 1)
+  c, continue              run until a breakpoint, watchpoint, halt or missing input
+  b, break <addr>          break before executing the instruction at <addr>
+  b, break op <mnemonic>   break before executing an instruction, e.g. `b op out`
+  b, break out <value>     break after <value> is written to the output
+  bl, breakpoints          list breakpoints
+  d, delete <n>            delete breakpoint number <n>
+  w, watch <addr>          stop when the memory cell at <addr> changes
+  uw, unwatch <addr>       remove a watchpoint
+  r, regs                  show ip, relative base and the next instruction
+  m, mem <addr> [len]      show a memory window (default 16 cells)
+  set <addr> <value>       modify a memory cell
+  i, input                 show pending input
+  i, input <values...>     append values to the pending input
+  i, input clear           remove all pending input
+  o, output                show the output so far
+  h, help                  show this message
+  q, quit                  exit";
+
+fn main() {
+    let path = env::args().nth(1).unwrap_or_else(|| {
+        eprintln!("Usage: intcode-debugger <program file>");
+        process::exit(2);
+    });
+    let source = read_to_string(&path).unwrap_or_else(|e| {
+        eprintln!("cannot read {}: {}", path, e);
+        process::exit(2);
+    });
+    let program = program::parse_from_string(&source).unwrap_or_else(|errors| {
+        for e in errors {
+            eprintln!("{}", e);
+        }
+        process::exit(2);
+    });
+
+    let mut debugger = Debugger::new(Computer::with_empty_input(program));
+    println!("Loaded {}. Type `help` for a list of commands.", path);
+    print_registers(&debugger);
+
+    let stdin = stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("(icd) ");
+        stdout().flush().unwrap();
+
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => break,
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+
+        let output_len = debugger.computer.output().len();
+        match execute(&mut debugger, &words) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(message) => println!("{}", message),
+        }
+
+        for value in &debugger.computer.output()[output_len..] {
+            println!("output: {}", value);
+        }
+    }
+}
+
+/// Executes a single command. Returns `false` if the debugger should exit.
+fn execute(debugger: &mut Debugger, words: &[&str]) -> Result<bool, String> {
+    match words {
+        ["s"] | ["step"] => {
+            let reason = debugger.step();
+            report_stop(debugger, reason);
+        }
+        ["s", n] | ["step", n] => {
+            let n: usize = parse(n)?;
+            let mut reason = StopReason::Stepped;
+            for _ in 0..n {
+                reason = debugger.step();
+                if reason != StopReason::Stepped {
+                    break;
+                }
+            }
+            report_stop(debugger, reason);
+        }
+        ["c"] | ["continue"] => {
+            let reason = debugger.resume();
+            report_stop(debugger, reason);
+        }
+        ["b", "op", mnemonic] | ["break", "op", mnemonic] => {
+            let opcode = Opcode::from_mnemonic(mnemonic)
+                .ok_or_else(|| format!("unknown mnemonic {}", mnemonic))?;
+            debugger.add_breakpoint(Breakpoint::Opcode(opcode));
+        }
+        ["b", "out", value] | ["break", "out", value] => {
+            debugger.add_breakpoint(Breakpoint::Output(parse(value)?));
+        }
+        ["b", addr] | ["break", addr] => {
+            debugger.add_breakpoint(Breakpoint::Address(parse(addr)?));
+        }
+        ["bl"] | ["breakpoints"] => {
+            for (i, breakpoint) in debugger.breakpoints().iter().enumerate() {
+                println!("{}: {}", i, breakpoint);
+            }
+            for addr in debugger.watchpoints() {
+                println!("watch [{}]", addr);
+            }
+        }
+        ["d", n] | ["delete", n] => {
+            debugger
+                .remove_breakpoint(parse(n)?)
+                .ok_or_else(|| format!("no breakpoint {}", n))?;
+        }
+        ["w", addr] | ["watch", addr] => debugger.watch(parse(addr)?),
+        ["uw", addr] | ["unwatch", addr] => {
+            if !debugger.unwatch(parse(addr)?) {
+                return Err(format!("{} is not watched", addr));
+            }
+        }
+        ["r"] | ["regs"] => print_registers(debugger),
+        ["m", addr] | ["mem", addr] => print_memory(debugger, parse(addr)?, 16),
+        ["m", addr, len] | ["mem", addr, len] => print_memory(debugger, parse(addr)?, parse(len)?),
+        ["set", addr, value] => debugger
+            .computer
+            .write_memory(parse(addr)?, parse(value)?)
+            .map_err(|e| e.to_string())?,
+        ["i"] | ["input"] => println!("pending input: {:?}", debugger.computer.input()),
+        ["i", "clear"] | ["input", "clear"] => debugger.computer.input_mut().clear(),
+        ["i", values @ ..] | ["input", values @ ..] => {
+            let values = values
+                .iter()
+                .map(|v| parse(v))
+                .collect::<Result<Vec<isize>, _>>()?;
+            debugger.computer.input_mut().extend(values);
+        }
+        ["o"] | ["output"] => println!("output: {:?}", debugger.computer.output()),
+        ["h"] | ["help"] => println!("{}", HELP),
+        ["q"] | ["quit"] => return Ok(false),
+        _ => return Err(format!("unknown command: {}", words.join(" "))),
+    }
+
+    Ok(true)
+}
+
+fn parse<T: std::str::FromStr>(s: &str) -> Result<T, String> {
+    s.parse().map_err(|_| format!("invalid number {}", s))
+}
+
+fn report_stop(debugger: &Debugger, reason: StopReason) {
+    if reason != StopReason::Stepped {
+        println!("{}", reason);
+    }
+    print_registers(debugger);
+}
+
+fn print_registers(debugger: &Debugger) {
+    let computer = &debugger.computer;
+    let ip = computer.ip();
+    // Stops at the last address rather than overflowing, but always includes `ip`
+    let cells: Vec<isize> = (0..4)
+        .filter_map(|offset| ip.checked_add(offset))
+        .map(|addr| computer.read_memory(addr))
+        .collect();
+
+    println!(
+        "ip={} rb={} next: {}",
+        ip,
+        computer.relative_base(),
+        disassemble_instruction(&cells).unwrap_or_else(|| format!(".data {}", cells[0]))
+    );
+}
+
+fn print_memory(debugger: &Debugger, start: usize, len: usize) {
+    let end = start.saturating_add(len);
+    for row_start in (start..end).step_by(8) {
+        let row: Vec<String> = (row_start..row_start.saturating_add(8).min(end))
+            .map(|addr| debugger.computer.read_memory(addr).to_string())
+            .collect();
+        println!("{:>6}: {}", row_start, row.join(" "));
+    }
+}
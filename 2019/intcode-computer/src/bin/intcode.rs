@@ -0,0 +1,315 @@
+use std::{
+    env,
+    fs::read_to_string,
+    io::{stderr, stdin, Read},
+    process,
+};
+
+use intcode_computer::{
+    profiler::Profiler, program, trace::JsonLinesTracer, Computer, IntcodeError, RunState,
+};
+
+const USAGE: &str = "Usage: intcode <program file> [options]
+
+Options:
+  -i, --input <n,n,...>    append numbers to the input (can be repeated)
+  -a, --ascii <text>       append a line of text to the input, as ASCII codes
+      --stdin              append numbers read from stdin, separated by commas or whitespace
+      --ascii-stdin        append the text read from stdin, as ASCII codes
+  -f, --format <format>    output format: numbers (default), ascii or json
+      --max-steps <n>      stop after executing n instructions
+      --trace              write every executed instruction to stderr as JSON lines
+      --profile            write a profile of the execution to stderr
+  -h, --help               show this message
+
+Exit status:
+  0  the program halted
+  1  the program failed
+  2  invalid arguments or program file
+  3  the program is waiting for input that was not given
+  4  the step limit was reached";
+
+const EXIT_HALTED: i32 = 0;
+const EXIT_FAILED: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_AWAITING_INPUT: i32 = 3;
+const EXIT_STEP_LIMIT: i32 = 4;
+
+/// Number of hotspots in the profile.
+const PROFILE_HOTSPOTS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Numbers,
+    Ascii,
+    Json,
+}
+
+struct Options {
+    path: String,
+    input: Vec<isize>,
+    format: Format,
+    max_steps: Option<u64>,
+    trace: bool,
+    profile: bool,
+}
+
+enum Outcome {
+    Halted,
+    AwaitingInput,
+    StepLimit,
+    Failed(IntcodeError),
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = parse_options(&args).unwrap_or_else(|message| {
+        eprintln!("{}\n\n{}", message, USAGE);
+        process::exit(EXIT_USAGE);
+    });
+
+    let source = read_to_string(&options.path).unwrap_or_else(|e| {
+        eprintln!("cannot read {}: {}", options.path, e);
+        process::exit(EXIT_USAGE);
+    });
+    let program = program::parse_from_string(&source).unwrap_or_else(|errors| {
+        for e in errors {
+            eprintln!("{}", e);
+        }
+        process::exit(EXIT_USAGE);
+    });
+
+    let mut computer = Computer::new(program.clone(), options.input.clone());
+    if options.trace {
+        computer.set_tracer(JsonLinesTracer::new(stderr()));
+    }
+    let profiler = if options.profile {
+        Some(Profiler::attach(&mut computer))
+    } else {
+        None
+    };
+
+    let outcome = run(&mut computer, options.max_steps);
+
+    print_output(computer.output(), options.format, &outcome);
+    if let Some(profiler) = profiler {
+        eprint!(
+            "{}",
+            profiler
+                .lock()
+                .unwrap()
+                .report(Some(&program), PROFILE_HOTSPOTS)
+        );
+    }
+
+    match &outcome {
+        Outcome::Halted => {}
+        Outcome::AwaitingInput => eprintln!("waiting for input at {}", computer.ip()),
+        Outcome::StepLimit => eprintln!("step limit reached at {}", computer.ip()),
+        Outcome::Failed(e) => eprintln!("{}", e),
+    }
+    process::exit(exit_code(&outcome));
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut path = None;
+    let mut options = Options {
+        path: String::new(),
+        input: Vec::new(),
+        format: Format::Numbers,
+        max_steps: None,
+        trace: false,
+        profile: false,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("missing value for {}", name))
+        };
+
+        match arg.as_str() {
+            "-i" | "--input" => options.input.extend(parse_numbers(value(arg)?)?),
+            "-a" | "--ascii" => {
+                options.input.extend(ascii_codes(value(arg)?));
+                options.input.push(b'\n' as isize);
+            }
+            "--stdin" => options.input.extend(parse_numbers(&read_stdin()?)?),
+            "--ascii-stdin" => options.input.extend(ascii_codes(&read_stdin()?)),
+            "-f" | "--format" => {
+                options.format = match value(arg)?.as_str() {
+                    "numbers" => Format::Numbers,
+                    "ascii" => Format::Ascii,
+                    "json" => Format::Json,
+                    format => return Err(format!("unknown format {}", format)),
+                }
+            }
+            "--max-steps" => {
+                let steps = value(arg)?;
+                options.max_steps = Some(
+                    steps
+                        .parse()
+                        .map_err(|_| format!("invalid step limit {}", steps))?,
+                );
+            }
+            "--trace" => options.trace = true,
+            "--profile" => options.profile = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(EXIT_HALTED);
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+            _ if path.is_none() => path = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+
+    options.path = path.ok_or_else(|| "missing program file".to_string())?;
+
+    Ok(options)
+}
+
+fn parse_numbers(s: &str) -> Result<Vec<isize>, String> {
+    s.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|n| !n.is_empty())
+        .map(|n| n.parse().map_err(|_| format!("invalid number {}", n)))
+        .collect()
+}
+
+fn ascii_codes(s: &str) -> impl Iterator<Item = isize> + '_ {
+    s.bytes().map(isize::from)
+}
+
+fn read_stdin() -> Result<String, String> {
+    let mut text = String::new();
+    stdin()
+        .read_to_string(&mut text)
+        .map_err(|e| format!("cannot read stdin: {}", e))?;
+
+    Ok(text)
+}
+
+fn run(computer: &mut Computer, max_steps: Option<u64>) -> Outcome {
+    let mut steps = 0;
+    loop {
+        if max_steps == Some(steps) {
+            return Outcome::StepLimit;
+        }
+        steps += 1;
+
+        match computer.step() {
+            Ok(Some(RunState::Halted)) => return Outcome::Halted,
+            Ok(Some(RunState::AwaitingInput)) => return Outcome::AwaitingInput,
+            Ok(_) => {}
+            Err(e) => return Outcome::Failed(e),
+        }
+    }
+}
+
+fn exit_code(outcome: &Outcome) -> i32 {
+    match outcome {
+        Outcome::Halted => EXIT_HALTED,
+        Outcome::AwaitingInput => EXIT_AWAITING_INPUT,
+        Outcome::StepLimit => EXIT_STEP_LIMIT,
+        Outcome::Failed(_) => EXIT_FAILED,
+    }
+}
+
+fn print_output(output: &[isize], format: Format, outcome: &Outcome) {
+    match format {
+        Format::Numbers => {
+            for value in output {
+                println!("{}", value);
+            }
+        }
+        // Values outside of the ASCII range are printed as numbers on their own lines
+        Format::Ascii => {
+            let mut text = String::new();
+            for &value in output {
+                if (0..128).contains(&value) {
+                    text.push(value as u8 as char);
+                } else {
+                    if !text.is_empty() && !text.ends_with('\n') {
+                        text.push('\n');
+                    }
+                    text.push_str(&format!("{}\n", value));
+                }
+            }
+            print!("{}", text);
+        }
+        Format::Json => {
+            let state = match outcome {
+                Outcome::Halted => "halted",
+                Outcome::AwaitingInput => "awaiting_input",
+                Outcome::StepLimit => "step_limit",
+                Outcome::Failed(_) => "failed",
+            };
+            let values: Vec<String> = output.iter().map(isize::to_string).collect();
+            println!(r#"{{"state":"{}","output":[{}]}}"#, state, values.join(","));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn rejects_unknown_option() {
+        assert_eq!(
+            parse_options(&args(&["prog", "--fast"])).err(),
+            Some("unknown option --fast".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_missing_value() {
+        assert_eq!(
+            parse_options(&args(&["prog", "--input"])).err(),
+            Some("missing value for --input".to_string())
+        );
+    }
+
+    #[test]
+    fn appends_newline_to_ascii_input() {
+        let options = parse_options(&args(&["prog", "-i", "1", "--ascii", "hi"])).unwrap();
+
+        assert_eq!(options.path, "prog");
+        assert_eq!(options.input, vec![1, 104, 105, 10]);
+    }
+
+    #[test]
+    fn stops_at_step_limit() {
+        let mut computer = Computer::new(vec![99], vec![]);
+        let outcome = run(&mut computer, Some(0));
+
+        assert!(matches!(outcome, Outcome::StepLimit));
+        assert_eq!(exit_code(&outcome), EXIT_STEP_LIMIT);
+        assert_eq!(computer.ip(), 0);
+    }
+
+    #[test]
+    fn runs_until_halt() {
+        let mut computer = Computer::new(vec![3, 0, 4, 0, 99], vec![7]);
+        let outcome = run(&mut computer, Some(3));
+
+        assert!(matches!(outcome, Outcome::Halted));
+        assert_eq!(exit_code(&outcome), EXIT_HALTED);
+        assert_eq!(computer.output(), &[7]);
+    }
+
+    #[test]
+    fn stops_when_input_runs_out() {
+        let mut computer = Computer::new(vec![3, 0, 3, 0, 99], vec![7]);
+        let outcome = run(&mut computer, None);
+
+        assert!(matches!(outcome, Outcome::AwaitingInput));
+        assert_eq!(exit_code(&outcome), EXIT_AWAITING_INPUT);
+        assert_eq!(computer.ip(), 2);
+    }
+}
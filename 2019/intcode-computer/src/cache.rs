@@ -0,0 +1,114 @@
+use crate::instruction::InstructionHeader;
+use crate::word::Word;
+
+/// An instruction as stored in memory: its header and the raw values of its arguments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodedInstruction<W = isize> {
+    pub header: InstructionHeader,
+    pub operands: [W; 3],
+}
+
+/// Instructions decoded from memory, by address.
+///
+/// Only the encoded form is cached, as position and relative mode arguments may point to
+/// different values each time the instruction runs. An entry is dropped when any of the cells it
+/// was decoded from is written to, so self-modifying programs keep working.
+#[derive(Clone)]
+pub struct InstructionCache<W = isize> {
+    entries: Vec<Option<EncodedInstruction<W>>>,
+}
+
+impl<W> Default for InstructionCache<W> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+}
+
+/// The longest instruction has a header and 3 arguments.
+const MAX_INSTRUCTION_LEN: usize = 4;
+/// Instructions at or above this address are not cached, so that the cache stays small when a
+/// program runs code far away in memory.
+const MAX_CACHED_ADDRESS: usize = 1 << 20;
+
+impl<W: Word> InstructionCache<W> {
+    pub fn get(&self, addr: usize) -> Option<&EncodedInstruction<W>> {
+        self.entries.get(addr).and_then(Option::as_ref)
+    }
+
+    pub fn insert(&mut self, addr: usize, instruction: EncodedInstruction<W>) {
+        if addr >= MAX_CACHED_ADDRESS {
+            return;
+        }
+        if self.entries.len() <= addr {
+            self.entries.resize(addr + 1, None);
+        }
+
+        self.entries[addr] = Some(instruction);
+    }
+
+    /// Drops the instructions that contain the cell at `addr`.
+    pub fn invalidate(&mut self, addr: usize) {
+        let first = addr.saturating_sub(MAX_INSTRUCTION_LEN - 1);
+        let end = (addr + 1).min(self.entries.len());
+
+        for start in first..end {
+            let covers_addr = match &self.entries[start] {
+                Some(instr) => start + instr.header.opcode.instruction_len() > addr,
+                None => false,
+            };
+            if covers_addr {
+                self.entries[start] = None;
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::{ArgMode, Opcode};
+
+    fn encoded(opcode: Opcode) -> EncodedInstruction {
+        let modes = vec![ArgMode::Position; opcode.args_count()];
+
+        EncodedInstruction {
+            header: InstructionHeader::new(opcode, &modes),
+            operands: [0; 3],
+        }
+    }
+
+    #[test]
+    fn invalidates_instructions_containing_the_address() {
+        let mut cache = InstructionCache::default();
+        cache.insert(0, encoded(Opcode::Add));
+        cache.insert(4, encoded(Opcode::WriteOutput));
+        cache.insert(6, encoded(Opcode::Halt));
+
+        cache.invalidate(5);
+        assert_eq!(cache.get(0), Some(&encoded(Opcode::Add)));
+        assert_eq!(cache.get(4), None);
+        assert_eq!(cache.get(6), Some(&encoded(Opcode::Halt)));
+
+        cache.invalidate(3);
+        assert_eq!(cache.get(0), None);
+
+        cache.invalidate(100);
+        assert_eq!(cache.get(6), Some(&encoded(Opcode::Halt)));
+    }
+
+    #[test]
+    fn invalidates_empty_cache() {
+        let mut cache = InstructionCache::<isize>::default();
+        for addr in 0..8 {
+            cache.invalidate(addr);
+        }
+
+        assert_eq!(cache.get(0), None);
+    }
+}
@@ -1,254 +1,666 @@
-use std::{cell::RefCell, rc::Rc};
+use std::collections::{BTreeMap, VecDeque};
 
-use crate::instruction::{ArgMode, Instruction};
-use crate::ram::RAM;
+use serde::{Deserialize, Serialize};
 
-pub struct Computer {
-    ram: RAM,
-    input: Rc<RefCell<Vec<isize>>>,
-    input_index: usize,
+use crate::cache::{EncodedInstruction, InstructionCache};
+use crate::error::IntcodeError;
+use crate::instruction::{ArgMode, HeaderError, Instruction, InstructionHeader, Opcode};
+use crate::io::{InputSource, OutputSink, RewindableInput, RewindableOutput};
+use crate::journal::{Journal, JournalEntry};
+use crate::ram::{MemoryError, PageStats, RAM};
+use crate::trace::{TraceStep, Tracer, Tracers};
+use crate::word::Word;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunState<W = isize> {
+    Halted,
+    AwaitingInput,
+    Output(W),
+}
+
+/// The full state of a `Computer`, including its pending input and the output so far.
+///
+/// Can be serialized with any serde format to resume a session later.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot<I = VecDeque<isize>, O = Vec<isize>, W = isize> {
+    /// Allocated pages of memory, by the address of their first cell.
+    pub memory: BTreeMap<usize, Vec<W>>,
+    pub ip: usize,
+    pub relative_base: isize,
+    pub input: I,
+    pub output: O,
+}
+
+/// An Intcode machine whose memory cells hold values of type `W`.
+///
+/// Addition and multiplication wrap around on overflow, unless overflow checks are enabled.
+pub struct Computer<I = VecDeque<isize>, O = Vec<isize>, W = isize> {
+    ram: RAM<W>,
+    input: I,
     relative_base: isize,
-    output: Rc<RefCell<Vec<isize>>>,
+    output: O,
     ip: usize,
+    /// Address of the most recently decoded instruction.
+    instr_ip: usize,
+    /// `None` when caching decoded instructions is disabled.
+    cache: Option<InstructionCache<W>>,
+    /// Decode headers with `InstructionHeader::parse_digits`.
+    string_decoding: bool,
+    overflow_checks: bool,
+    /// `None` when journaling is disabled.
+    journal: Option<Journal<W>>,
+    tracer: Option<Box<dyn Tracer + Send>>,
 }
 
 impl Computer {
-    pub fn new(memory: Vec<isize>, input: Rc<RefCell<Vec<isize>>>) -> Self {
-        Self::with_output(memory, input, Rc::new(RefCell::new(Vec::new())))
+    pub fn new(memory: Vec<isize>, input: Vec<isize>) -> Self {
+        Self::with_io(memory, input.into(), Vec::new())
     }
 
-    pub fn with_output(
-        program: Vec<isize>,
-        input: Rc<RefCell<Vec<isize>>>,
-        output: Rc<RefCell<Vec<isize>>>,
-    ) -> Self {
+    pub fn with_empty_input(memory: Vec<isize>) -> Self {
+        Self::new(memory, Vec::new())
+    }
+}
+
+/// Clones everything except the tracer, which is not carried over to the clone.
+impl<I: Clone, O: Clone, W: Word> Clone for Computer<I, O, W> {
+    fn clone(&self) -> Self {
+        Self {
+            ram: self.ram.clone(),
+            input: self.input.clone(),
+            relative_base: self.relative_base,
+            output: self.output.clone(),
+            ip: self.ip,
+            instr_ip: self.instr_ip,
+            cache: self.cache.clone(),
+            string_decoding: self.string_decoding,
+            overflow_checks: self.overflow_checks,
+            journal: self.journal.clone(),
+            tracer: None,
+        }
+    }
+}
+
+impl<I: Clone, O: Clone, W: Word> Computer<I, O, W> {
+    pub fn snapshot(&self) -> Snapshot<I, O, W> {
+        Snapshot {
+            memory: self.ram.pages(),
+            ip: self.ip,
+            relative_base: self.relative_base,
+            input: self.input.clone(),
+            output: self.output.clone(),
+        }
+    }
+
+    /// Brings the computer back to the state from the snapshot. The tracer and the memory
+    /// settings are kept. The journal is emptied.
+    pub fn restore(&mut self, snapshot: &Snapshot<I, O, W>) {
+        self.ram.load_pages(&snapshot.memory);
+        self.ip = snapshot.ip;
+        self.instr_ip = snapshot.ip;
+        self.relative_base = snapshot.relative_base;
+        self.input = snapshot.input.clone();
+        self.output = snapshot.output.clone();
+        if let Some(cache) = self.cache.as_mut() {
+            cache.clear();
+        }
+        if let Some(journal) = self.journal.as_mut() {
+            journal.clear();
+        }
+    }
+}
+
+impl<I: InputSource<W>, O: OutputSink<W>, W: Word> Computer<I, O, W> {
+    pub fn with_io(program: Vec<W>, input: I, output: O) -> Self {
         Self {
             ram: RAM::new(program),
             input,
             output,
-            input_index: 0,
             relative_base: 0,
             ip: 0,
+            instr_ip: 0,
+            cache: Some(InstructionCache::default()),
+            string_decoding: false,
+            overflow_checks: false,
+            journal: None,
+            tracer: None,
         }
     }
 
-    pub fn with_empty_input(memory: Vec<isize>) -> Self {
-        Self::new(memory, Rc::new(RefCell::new(Vec::new())))
+    pub fn from_snapshot(snapshot: Snapshot<I, O, W>) -> Self {
+        Self {
+            ram: RAM::from_pages(&snapshot.memory),
+            input: snapshot.input,
+            output: snapshot.output,
+            relative_base: snapshot.relative_base,
+            ip: snapshot.ip,
+            instr_ip: snapshot.ip,
+            cache: Some(InstructionCache::default()),
+            string_decoding: false,
+            overflow_checks: false,
+            journal: None,
+            tracer: None,
+        }
+    }
+
+    /// Makes additions and multiplications whose result does not fit in the word type fail with
+    /// `Overflow`, instead of wrapping around. Disabled by default.
+    pub fn set_overflow_checks(&mut self, enabled: bool) {
+        self.overflow_checks = enabled;
+    }
+
+    /// Makes accessing memory at or above `limit` fail with `MemoryLimitExceeded`. Unlimited by
+    /// default.
+    pub fn set_memory_limit(&mut self, limit: Option<usize>) {
+        self.ram.set_limit(limit);
+    }
+
+    /// Makes writes to the cells of the original program fail with `WriteToProtectedMemory`.
+    /// Disabled by default.
+    pub fn set_image_protection(&mut self, enabled: bool) {
+        self.ram.set_image_protection(enabled);
+    }
+
+    /// Numbers of memory reads and writes done by the program, by page.
+    pub fn memory_stats(&self) -> Vec<PageStats> {
+        self.ram.stats()
     }
 
-    pub fn run_till_halt(&mut self) {
-        while self.parse_and_exec_once() != Instruction::Halt {}
+    /// Enables or disables caching of decoded instructions. Enabled by default.
+    pub fn set_instruction_cache(&mut self, enabled: bool) {
+        self.cache = if enabled {
+            Some(InstructionCache::default())
+        } else {
+            None
+        };
     }
 
-    pub fn parse_and_exec_once(&mut self) -> Instruction {
-        let instr = self.parse_instruction();
-        self.exec(&instr);
+    /// Decodes instructions by formatting their headers as strings, as the computer originally
+    /// did. Only meant to measure the speedup of the arithmetic decoding in the benchmarks.
+    #[doc(hidden)]
+    pub fn set_string_decoding(&mut self, enabled: bool) {
+        self.string_decoding = enabled;
+    }
 
-        instr
+    /// Records the changes made by the last `capacity` instructions executed by `step` (and so
+    /// by `run` and `run_till_halt`), so that they can be undone with `step_back`. `None`
+    /// disables journaling, which is the default.
+    ///
+    /// Changes made with `write_memory`, `input_mut` or `output_mut` are not recorded.
+    pub fn set_journal(&mut self, capacity: Option<usize>) {
+        self.journal = capacity.map(Journal::new);
     }
 
-    pub fn output(&self) -> std::cell::Ref<Vec<isize>> {
-        self.output.borrow()
+    /// Number of instructions that can be undone.
+    pub fn journal_len(&self) -> usize {
+        self.journal.as_ref().map_or(0, Journal::len)
     }
 
-    pub fn exec(&mut self, instr: &Instruction) {
-        match *instr {
+    pub fn run_till_halt(&mut self) -> Result<(), IntcodeError> {
+        loop {
+            match self.run()? {
+                RunState::Halted => return Ok(()),
+                RunState::AwaitingInput => {
+                    return Err(IntcodeError::InputExhausted { ip: self.ip })
+                }
+                RunState::Output(_) => {}
+            }
+        }
+    }
+
+    /// Runs the program until it halts, produces an output or needs an input value that is not
+    /// available yet.
+    ///
+    /// When stopping because of a missing input or a halt, the instruction pointer is left on
+    /// that instruction, so `run` can be called again after providing more input.
+    pub fn run(&mut self) -> Result<RunState<W>, IntcodeError> {
+        loop {
+            if let Some(state) = self.step()? {
+                return Ok(state);
+            }
+        }
+    }
+
+    /// Executes a single instruction. Returns the state `run` would stop in after this
+    /// instruction, or `None` if `run` would continue.
+    pub fn step(&mut self) -> Result<Option<RunState<W>>, IntcodeError> {
+        let ip = self.ip;
+        let relative_base = self.relative_base;
+        let instr = self.parse_instruction()?;
+
+        let journaling = self.journal.is_some();
+        let write = match instr.written_address() {
+            Some(addr) if journaling => Some((addr, self.ram.peek(addr))),
+            _ => None,
+        };
+        let mut input = None;
+
+        match instr {
+            Instruction::ReadInput { to } => match self.input.read() {
+                Some(value) => {
+                    if journaling {
+                        input = Some(value.clone());
+                    }
+                    self.store(to, value)?;
+                }
+                None => {
+                    self.ip = ip;
+                    return Ok(Some(RunState::AwaitingInput));
+                }
+            },
+            Instruction::Halt => {
+                self.ip = ip;
+                return Ok(Some(RunState::Halted));
+            }
+            _ => self.exec(&instr)?,
+        }
+
+        self.record_step(ip, &instr);
+        if let Some(journal) = self.journal.as_mut() {
+            journal.push(JournalEntry {
+                ip,
+                relative_base,
+                write,
+                input,
+                output: matches!(instr, Instruction::WriteOutput { .. }),
+            });
+        }
+
+        match instr {
+            Instruction::WriteOutput { val } => Ok(Some(RunState::Output(val))),
+            _ => Ok(None),
+        }
+    }
+
+    /// Records every instruction executed by `step` (and so by `run` and `run_till_halt`).
+    /// Instructions that stop the execution without taking effect (a halt or a read with no
+    /// input available) are not recorded. Values that do not fit in an `isize` are recorded as
+    /// `isize::MIN` or `isize::MAX`.
+    pub fn set_tracer(&mut self, tracer: impl Tracer + Send + 'static) {
+        self.tracer = Some(Box::new(tracer));
+    }
+
+    /// Like `set_tracer`, but keeps the current tracer, so that both record every instruction.
+    pub fn add_tracer(&mut self, tracer: impl Tracer + Send + 'static) {
+        self.tracer = Some(match self.tracer.take() {
+            Some(current) => Box::new(Tracers(vec![current, Box::new(tracer)])),
+            None => Box::new(tracer),
+        });
+    }
+
+    pub fn take_tracer(&mut self) -> Option<Box<dyn Tracer + Send>> {
+        self.tracer.take()
+    }
+
+    fn record_step(&mut self, ip: usize, instruction: &Instruction<W>) {
+        if let Some(tracer) = self.tracer.as_mut() {
+            let ram = &self.ram;
+            tracer.record(&TraceStep {
+                ip,
+                instruction: instruction.map(W::to_isize_saturating),
+                write: instruction
+                    .written_address()
+                    .map(|addr| (addr, ram.peek(addr).to_isize_saturating())),
+                relative_base: match instruction {
+                    Instruction::AdjustRelativeBase { .. } => Some(self.relative_base),
+                    _ => None,
+                },
+            });
+        }
+    }
+
+    pub fn parse_and_exec_once(&mut self) -> Result<Instruction<W>, IntcodeError> {
+        let instr = self.parse_instruction()?;
+        self.exec(&instr)?;
+
+        Ok(instr)
+    }
+
+    /// Address of the next instruction to execute.
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn relative_base(&self) -> isize {
+        self.relative_base
+    }
+
+    /// Reads a memory cell without growing the memory.
+    pub fn read_memory(&self, addr: usize) -> W {
+        self.ram.peek(addr)
+    }
+
+    pub fn write_memory(&mut self, addr: usize, value: W) -> Result<(), IntcodeError> {
+        self.store(addr, value)
+    }
+
+    pub fn input(&self) -> &I {
+        &self.input
+    }
+
+    pub fn input_mut(&mut self) -> &mut I {
+        &mut self.input
+    }
+
+    pub fn output(&self) -> &O {
+        &self.output
+    }
+
+    pub fn output_mut(&mut self) -> &mut O {
+        &mut self.output
+    }
+
+    /// Executes an instruction decoded by `parse_instruction`.
+    pub fn exec(&mut self, instr: &Instruction<W>) -> Result<(), IntcodeError> {
+        match instr {
             Instruction::Add { arg1, arg2, out } => {
-                self.ram.set(out, arg1 + arg2);
+                let sum = if self.overflow_checks {
+                    arg1.checked_add(arg2).ok_or_else(|| self.overflow())?
+                } else {
+                    arg1.wrapping_add(arg2)
+                };
+                self.store(*out, sum)?;
             }
             Instruction::Multiply { arg1, arg2, out } => {
-                self.ram.set(out, arg1 * arg2);
+                let product = if self.overflow_checks {
+                    arg1.checked_mul(arg2).ok_or_else(|| self.overflow())?
+                } else {
+                    arg1.wrapping_mul(arg2)
+                };
+                self.store(*out, product)?;
             }
             Instruction::ReadInput { to } => {
-                let value_read = *self
+                let value_read = self
                     .input
-                    .borrow()
-                    .get(self.input_index)
-                    .expect("Input too short");
-                self.ram.set(to, value_read);
-                self.input_index += 1;
-                if self.input.borrow().len() != self.input_index {
-                    println!("mismatch");
-                }
+                    .read()
+                    .ok_or(IntcodeError::InputExhausted { ip: self.instr_ip })?;
+                self.store(*to, value_read)?;
             }
             Instruction::WriteOutput { val } => {
-                self.output.borrow_mut().push(val);
+                self.output.write(val.clone());
             }
             Instruction::JumpIfTrue { arg, destination } => {
-                if arg != 0 {
-                    self.ip = destination;
+                if !arg.is_zero() {
+                    self.ip = *destination;
                 }
             }
             Instruction::JumpIfFalse { arg, destination } => {
-                if arg == 0 {
-                    self.ip = destination;
+                if arg.is_zero() {
+                    self.ip = *destination;
                 }
             }
             Instruction::LessThan { arg1, arg2, out } => {
-                let result = if arg1 < arg2 { 1 } else { 0 };
-                self.ram.set(out, result);
+                let result = if arg1 < arg2 { W::one() } else { W::zero() };
+                self.store(*out, result)?;
             }
             Instruction::Equals { arg1, arg2, out } => {
-                let result = if arg1 == arg2 { 1 } else { 0 };
-                self.ram.set(out, result);
+                let result = if arg1 == arg2 { W::one() } else { W::zero() };
+                self.store(*out, result)?;
             }
             Instruction::AdjustRelativeBase { change } => {
-                self.relative_base += change;
+                let change = self.to_isize(change)?;
+                self.relative_base = if self.overflow_checks {
+                    self.relative_base
+                        .checked_add(change)
+                        .ok_or_else(|| self.overflow())?
+                } else {
+                    self.relative_base.wrapping_add(change)
+                };
             }
             Instruction::Halt => {}
         }
+
+        Ok(())
     }
 
-    pub fn parse_instruction(&mut self) -> Instruction {
-        let instr = self.ram.get(self.ip);
-        let instr_digits = format!("{:0>5}", instr.to_string());
+    pub fn parse_instruction(&mut self) -> Result<Instruction<W>, IntcodeError> {
+        self.instr_ip = self.ip;
+        let encoded = self.fetch()?;
 
-        let opcode = instr_digits.get(3..).unwrap();
+        let instr = match encoded.header.opcode {
+            Opcode::Add => Instruction::Add {
+                arg1: self.get_arg(&encoded, 1)?,
+                arg2: self.get_arg(&encoded, 2)?,
+                out: self.get_addr_arg(&encoded, 3)?,
+            },
+            Opcode::Multiply => Instruction::Multiply {
+                arg1: self.get_arg(&encoded, 1)?,
+                arg2: self.get_arg(&encoded, 2)?,
+                out: self.get_addr_arg(&encoded, 3)?,
+            },
+            Opcode::ReadInput => Instruction::ReadInput {
+                to: self.get_addr_arg(&encoded, 1)?,
+            },
+            Opcode::WriteOutput => Instruction::WriteOutput {
+                val: self.get_arg(&encoded, 1)?,
+            },
+            Opcode::JumpIfTrue => Instruction::JumpIfTrue {
+                arg: self.get_arg(&encoded, 1)?,
+                destination: self.get_jump_destination(&encoded, 2)?,
+            },
+            Opcode::JumpIfFalse => Instruction::JumpIfFalse {
+                arg: self.get_arg(&encoded, 1)?,
+                destination: self.get_jump_destination(&encoded, 2)?,
+            },
+            Opcode::LessThan => Instruction::LessThan {
+                arg1: self.get_arg(&encoded, 1)?,
+                arg2: self.get_arg(&encoded, 2)?,
+                out: self.get_addr_arg(&encoded, 3)?,
+            },
+            Opcode::Equals => Instruction::Equals {
+                arg1: self.get_arg(&encoded, 1)?,
+                arg2: self.get_arg(&encoded, 2)?,
+                out: self.get_addr_arg(&encoded, 3)?,
+            },
+            Opcode::AdjustRelativeBase => Instruction::AdjustRelativeBase {
+                change: self.get_arg(&encoded, 1)?,
+            },
+            Opcode::Halt => Instruction::Halt,
+        };
 
-        match opcode {
-            "01" => {
-                let instr = Instruction::Add {
-                    arg1: self.get_arg(&instr_digits, 1),
-                    arg2: self.get_arg(&instr_digits, 2),
-                    out: self.get_addr_arg(&instr_digits, 3),
-                };
+        self.ip += encoded.header.opcode.instruction_len();
 
-                self.ip += 4;
+        Ok(instr)
+    }
 
-                instr
-            }
-            "02" => {
-                let instr = Instruction::Multiply {
-                    arg1: self.get_arg(&instr_digits, 1),
-                    arg2: self.get_arg(&instr_digits, 2),
-                    out: self.get_addr_arg(&instr_digits, 3),
-                };
+    /// Reads the instruction at `ip` from the cache, or decodes it from memory.
+    fn fetch(&mut self) -> Result<EncodedInstruction<W>, IntcodeError> {
+        if let Some(encoded) = self.cache.as_ref().and_then(|c| c.get(self.ip)) {
+            return Ok(encoded.clone());
+        }
 
-                self.ip += 4;
+        let value = self.load(self.ip)?.to_isize_saturating();
+        let header = if self.string_decoding {
+            InstructionHeader::parse_digits(value)
+        } else {
+            InstructionHeader::parse(value)
+        };
+        let header = header.map_err(|e| match e {
+            HeaderError::InvalidOpcode => IntcodeError::InvalidOpcode { ip: self.ip, value },
+            HeaderError::InvalidMode { arg_index } => IntcodeError::InvalidMode {
+                ip: self.ip,
+                value,
+                arg_index,
+            },
+        })?;
 
-                instr
-            }
-            "03" => {
-                let instr = Instruction::ReadInput {
-                    to: self.get_addr_arg(&instr_digits, 1),
-                };
+        let mut operands = [W::zero(), W::zero(), W::zero()];
+        for (i, operand) in operands
+            .iter_mut()
+            .take(header.opcode.args_count())
+            .enumerate()
+        {
+            *operand = self.load(self.ip + i + 1)?;
+        }
 
-                self.ip += 2;
+        let encoded = EncodedInstruction { header, operands };
+        if let Some(cache) = self.cache.as_mut() {
+            cache.insert(self.ip, encoded.clone());
+        }
 
-                instr
-            }
-            "04" => {
-                let instr = Instruction::WriteOutput {
-                    val: self.get_arg(&instr_digits, 1),
-                };
+        Ok(encoded)
+    }
 
-                self.ip += 2;
+    fn load(&mut self, addr: usize) -> Result<W, IntcodeError> {
+        self.ram.get(addr).map_err(|e| self.memory_error(e))
+    }
 
-                instr
-            }
-            "05" => {
-                let instr = Instruction::JumpIfTrue {
-                    arg: self.get_arg(&instr_digits, 1),
-                    destination: self.get_arg(&instr_digits, 2) as usize,
-                };
+    /// Writes to memory, dropping any cached instruction containing the cell.
+    fn store(&mut self, addr: usize, value: W) -> Result<(), IntcodeError> {
+        self.ram
+            .set(addr, value)
+            .map_err(|e| self.memory_error(e))?;
+        if let Some(cache) = self.cache.as_mut() {
+            cache.invalidate(addr);
+        }
 
-                self.ip += 3;
+        Ok(())
+    }
 
-                instr
-            }
-            "06" => {
-                let instr = Instruction::JumpIfFalse {
-                    arg: self.get_arg(&instr_digits, 1),
-                    destination: self.get_arg(&instr_digits, 2) as usize,
-                };
+    fn memory_error(&self, error: MemoryError) -> IntcodeError {
+        match error {
+            MemoryError::LimitExceeded { address } => IntcodeError::MemoryLimitExceeded {
+                ip: self.instr_ip,
+                address,
+            },
+            MemoryError::ProtectedWrite { address } => IntcodeError::WriteToProtectedMemory {
+                ip: self.instr_ip,
+                address,
+            },
+        }
+    }
 
-                self.ip += 3;
+    fn get_arg(
+        &mut self,
+        encoded: &EncodedInstruction<W>,
+        arg_index: usize,
+    ) -> Result<W, IntcodeError> {
+        let v = &encoded.operands[arg_index - 1];
 
-                instr
-            }
-            "07" => {
-                let instr = Instruction::LessThan {
-                    arg1: self.get_arg(&instr_digits, 1),
-                    arg2: self.get_arg(&instr_digits, 2),
-                    out: self.get_addr_arg(&instr_digits, 3),
-                };
+        let addr = match encoded.header.mode(arg_index) {
+            ArgMode::Immediate => return Ok(v.clone()),
+            ArgMode::Position => self.to_isize(v)?,
+            ArgMode::Relative => self.relative_address(v)?,
+        };
 
-                self.ip += 4;
+        self.load(self.to_address(addr)?)
+    }
 
-                instr
+    fn get_addr_arg(
+        &self,
+        encoded: &EncodedInstruction<W>,
+        arg_index: usize,
+    ) -> Result<usize, IntcodeError> {
+        let v = &encoded.operands[arg_index - 1];
+        let addr = match encoded.header.mode(arg_index) {
+            ArgMode::Position => self.to_isize(v)?,
+            ArgMode::Relative => self.relative_address(v)?,
+            ArgMode::Immediate => {
+                return Err(IntcodeError::WriteToImmediate {
+                    ip: self.ip,
+                    arg_index,
+                })
             }
-            "08" => {
-                let instr = Instruction::Equals {
-                    arg1: self.get_arg(&instr_digits, 1),
-                    arg2: self.get_arg(&instr_digits, 2),
-                    out: self.get_addr_arg(&instr_digits, 3),
-                };
+        };
 
-                self.ip += 4;
+        self.to_address(addr)
+    }
 
-                instr
-            }
-            "09" => {
-                let instr = Instruction::AdjustRelativeBase {
-                    change: self.get_arg(&instr_digits, 1),
-                };
+    fn get_jump_destination(
+        &mut self,
+        encoded: &EncodedInstruction<W>,
+        arg_index: usize,
+    ) -> Result<usize, IntcodeError> {
+        let destination = self.get_arg(encoded, arg_index)?;
 
-                self.ip += 2;
+        self.to_address(self.to_isize(&destination)?)
+    }
 
-                instr
-            }
-            "99" => {
-                self.ip += 1;
+    fn relative_address(&self, offset: &W) -> Result<isize, IntcodeError> {
+        self.to_isize(offset)?
+            .checked_add(self.relative_base)
+            .ok_or_else(|| self.overflow())
+    }
 
-                Instruction::Halt
-            }
-            _ => panic!("Invalid opcode {}", opcode),
-        }
+    /// Converts a value used as an address or a relative base change.
+    fn to_isize(&self, value: &W) -> Result<isize, IntcodeError> {
+        value.to_isize().ok_or_else(|| self.overflow())
+    }
+
+    fn overflow(&self) -> IntcodeError {
+        IntcodeError::Overflow { ip: self.instr_ip }
     }
 
-    fn get_arg(&mut self, instr_digits: &str, arg_index: usize) -> isize {
-        let arg_mode = instr_digits.as_bytes()[3 - arg_index] as char;
-        let arg_mode = ArgMode::parse(arg_mode);
+    fn to_address(&self, addr: isize) -> Result<usize, IntcodeError> {
+        if addr < 0 {
+            return Err(IntcodeError::NegativeAddress {
+                ip: self.ip,
+                address: addr,
+            });
+        }
 
-        self.get_arg_with_mode(arg_mode, arg_index)
+        Ok(addr as usize)
     }
+}
 
-    fn get_arg_with_mode(&mut self, mode: ArgMode, arg_index: usize) -> isize {
-        let v = self.ram.get(self.ip + arg_index);
+impl<I: RewindableInput<W>, O: RewindableOutput<W>, W: Word> Computer<I, O, W> {
+    /// Undoes the most recent instruction in the journal, putting back the overwritten memory
+    /// cell, the instruction pointer, the relative base, and the input value read or the output
+    /// value written. Returns `false` if there is nothing to undo.
+    ///
+    /// Undone instructions are not removed from the tracer.
+    pub fn step_back(&mut self) -> bool {
+        let entry = match self.journal.as_mut().and_then(Journal::pop) {
+            Some(entry) => entry,
+            None => return false,
+        };
 
-        match mode {
-            ArgMode::Immediate => v,
-            ArgMode::Position => self.ram.get(v as usize),
-            ArgMode::Relative => self.ram.get((v + self.relative_base) as usize),
+        if let Some((addr, value)) = entry.write {
+            self.ram.poke(addr, value);
+            if let Some(cache) = self.cache.as_mut() {
+                cache.invalidate(addr);
+            }
         }
-    }
+        if let Some(value) = entry.input {
+            self.input.unread(value);
+        }
+        if entry.output {
+            self.output.unwrite();
+        }
+        self.ip = entry.ip;
+        self.instr_ip = entry.ip;
+        self.relative_base = entry.relative_base;
 
-    fn get_addr_arg(&mut self, instr_digits: &str, arg_index: usize) -> usize {
-        let arg_mode = instr_digits.as_bytes()[3 - arg_index] as char;
-        let arg_mode = ArgMode::parse(arg_mode);
+        true
+    }
 
-        let mut addr = self.get_arg_with_mode(ArgMode::Immediate, arg_index);
-        if arg_mode == ArgMode::Relative {
-            addr += self.relative_base;
+    /// Steps back until `predicate` holds, checking it after every undone instruction. Returns
+    /// `false` if the journal runs out first.
+    pub fn run_back_until(&mut self, mut predicate: impl FnMut(&Self) -> bool) -> bool {
+        while self.step_back() {
+            if predicate(self) {
+                return true;
+            }
         }
 
-        addr as usize
+        false
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{cell::RefCell, rc::Rc};
+    use std::{cell::RefCell, collections::VecDeque, rc::Rc};
 
     use crate::instruction::Instruction;
 
-    use super::Computer;
+    use super::{Computer, IntcodeError, PageStats, RunState, Snapshot};
+    use crate::ram::PAGE_SIZE;
 
     #[test]
     fn correctly_parses_basic_multiply_instruction() {
         let mut computer = Computer::with_empty_input(vec![1002, 4, 3, 4, 33]);
 
-        let instr = computer.parse_instruction();
+        let instr = computer.parse_instruction().unwrap();
         assert_eq!(
             instr,
             Instruction::Multiply {
@@ -263,33 +675,33 @@ mod tests {
     fn correctly_invokes_basic_instructions() {
         let mut computer = Computer::with_empty_input(vec![1002, 4, 3, 4, 33]);
 
-        computer.parse_and_exec_once();
+        computer.parse_and_exec_once().unwrap();
         assert_eq!(computer.ip, 4, "Invalid IP");
 
-        assert_eq!(computer.parse_and_exec_once(), Instruction::Halt);
+        assert_eq!(computer.parse_and_exec_once(), Ok(Instruction::Halt));
     }
 
     #[test]
     fn jump_position_mode() {
         let input = vec![3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
-        let mut computer = Computer::new(input.clone(), Rc::new(RefCell::new(vec![0])));
-        computer.run_till_halt();
+        let mut computer = Computer::new(input.clone(), vec![0]);
+        computer.run_till_halt().unwrap();
         assert_eq!(computer.output().clone(), vec![0], "jump 0 test");
 
-        let mut computer = Computer::new(input, Rc::new(RefCell::new(vec![5])));
-        computer.run_till_halt();
+        let mut computer = Computer::new(input, vec![5]);
+        computer.run_till_halt().unwrap();
         assert_eq!(computer.output().clone(), vec![1], "jump non-0 test");
     }
 
     #[test]
     fn jump_immediate_mode() {
         let input = vec![3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1];
-        let mut computer = Computer::new(input.clone(), Rc::new(RefCell::new(vec![0])));
-        computer.run_till_halt();
+        let mut computer = Computer::new(input.clone(), vec![0]);
+        computer.run_till_halt().unwrap();
         assert_eq!(computer.output().clone(), vec![0], "jump 0 test");
 
-        let mut computer = Computer::new(input, Rc::new(RefCell::new(vec![5])));
-        computer.run_till_halt();
+        let mut computer = Computer::new(input, vec![5]);
+        computer.run_till_halt().unwrap();
         assert_eq!(computer.output().clone(), vec![1], "jump non-0 test");
     }
 
@@ -300,16 +712,16 @@ mod tests {
             0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
             20, 1105, 1, 46, 98, 99,
         ];
-        let mut computer = Computer::new(input.clone(), Rc::new(RefCell::new(vec![7])));
-        computer.run_till_halt();
+        let mut computer = Computer::new(input.clone(), vec![7]);
+        computer.run_till_halt().unwrap();
         assert_eq!(computer.output().clone(), vec![999], "below 8");
 
-        let mut computer = Computer::new(input.clone(), Rc::new(RefCell::new(vec![8])));
-        computer.run_till_halt();
+        let mut computer = Computer::new(input.clone(), vec![8]);
+        computer.run_till_halt().unwrap();
         assert_eq!(computer.output().clone(), vec![1000], "8");
 
-        let mut computer = Computer::new(input, Rc::new(RefCell::new(vec![9])));
-        computer.run_till_halt();
+        let mut computer = Computer::new(input, vec![9]);
+        computer.run_till_halt().unwrap();
         assert_eq!(computer.output().clone(), vec![1001], "above 9");
     }
 
@@ -318,16 +730,16 @@ mod tests {
         let input = vec![
             109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
         ];
-        let mut computer = Computer::new(input.clone(), Rc::new(RefCell::new(vec![])));
-        computer.run_till_halt();
+        let mut computer = Computer::new(input.clone(), vec![]);
+        computer.run_till_halt().unwrap();
         assert_eq!(computer.output().clone(), input);
     }
 
     #[test]
     fn output_16_digit_number() {
         let input = vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0];
-        let mut computer = Computer::new(input.clone(), Rc::new(RefCell::new(vec![])));
-        computer.run_till_halt();
+        let mut computer = Computer::new(input.clone(), vec![]);
+        computer.run_till_halt().unwrap();
         let output = computer.output();
 
         assert_eq!(output.len(), 1, "invalid length");
@@ -339,13 +751,355 @@ mod tests {
     #[test]
     fn handle_large_numbers() {
         let input = vec![104, 1125899906842624, 99];
-        let mut computer = Computer::new(input.clone(), Rc::new(RefCell::new(vec![])));
-        computer.run_till_halt();
-        let output = computer.output.borrow();
+        let mut computer = Computer::new(input.clone(), vec![]);
+        computer.run_till_halt().unwrap();
+        let output = &computer.output;
 
         assert_eq!(output.len(), 1, "invalid length");
 
         let output_num = output[0];
         assert_eq!(output_num, 1125899906842624, "invalid output number");
     }
+
+    #[test]
+    fn run_stops_on_output_and_missing_input() {
+        let mut computer = Computer::with_empty_input(vec![3, 9, 4, 9, 3, 10, 4, 10, 99, 0, 0]);
+
+        assert_eq!(computer.run(), Ok(RunState::AwaitingInput));
+        assert_eq!(
+            computer.run(),
+            Ok(RunState::AwaitingInput),
+            "not idempotent"
+        );
+
+        computer.input_mut().push_back(7);
+        assert_eq!(computer.run(), Ok(RunState::Output(7)));
+        assert_eq!(computer.run(), Ok(RunState::AwaitingInput));
+
+        computer.input_mut().push_back(-3);
+        assert_eq!(computer.run(), Ok(RunState::Output(-3)));
+        assert_eq!(computer.run(), Ok(RunState::Halted));
+        assert_eq!(
+            computer.run(),
+            Ok(RunState::Halted),
+            "halted machine resumed"
+        );
+        assert_eq!(computer.output().clone(), vec![7, -3]);
+    }
+
+    #[test]
+    fn custom_input_and_output() {
+        let written = Rc::new(RefCell::new(VecDeque::new()));
+        let sink = Rc::clone(&written);
+        let mut computer: Computer<_, _> = Computer::with_io(
+            vec![3, 9, 1002, 9, 2, 9, 4, 9, 99, 0],
+            || Some(21),
+            move |v| sink.borrow_mut().push_back(v),
+        );
+
+        computer.run_till_halt().unwrap();
+        assert_eq!(written.borrow_mut().pop_front(), Some(42));
+    }
+
+    #[test]
+    fn reports_invalid_opcode() {
+        let mut computer = Computer::with_empty_input(vec![1101, 1, 1, 5, 42, 0]);
+
+        assert_eq!(
+            computer.run_till_halt(),
+            Err(IntcodeError::InvalidOpcode { ip: 4, value: 42 })
+        );
+    }
+
+    #[test]
+    fn reports_invalid_mode() {
+        let mut computer = Computer::with_empty_input(vec![3101, 1, 1, 5, 99]);
+
+        assert_eq!(
+            computer.run_till_halt(),
+            Err(IntcodeError::InvalidMode {
+                ip: 0,
+                value: 3101,
+                arg_index: 2
+            })
+        );
+    }
+
+    #[test]
+    fn reports_negative_address() {
+        let mut computer = Computer::with_empty_input(vec![4, -3, 99]);
+        assert_eq!(
+            computer.run_till_halt(),
+            Err(IntcodeError::NegativeAddress { ip: 0, address: -3 })
+        );
+
+        let mut computer = Computer::with_empty_input(vec![109, -10, 22201, 0, 0, 0, 99]);
+        assert_eq!(
+            computer.run_till_halt(),
+            Err(IntcodeError::NegativeAddress {
+                ip: 2,
+                address: -10
+            })
+        );
+
+        let mut computer = Computer::with_empty_input(vec![1105, 1, -1]);
+        assert_eq!(
+            computer.run_till_halt(),
+            Err(IntcodeError::NegativeAddress { ip: 0, address: -1 })
+        );
+    }
+
+    #[test]
+    fn reports_exhausted_input() {
+        let mut computer = Computer::new(vec![3, 0, 3, 0, 99], vec![1]);
+        assert_eq!(
+            computer.run_till_halt(),
+            Err(IntcodeError::InputExhausted { ip: 2 })
+        );
+
+        let mut computer = Computer::with_empty_input(vec![3, 0, 99]);
+        assert_eq!(
+            computer.parse_and_exec_once(),
+            Err(IntcodeError::InputExhausted { ip: 0 })
+        );
+    }
+
+    #[test]
+    fn reports_write_to_immediate() {
+        let mut computer = Computer::with_empty_input(vec![11101, 1, 1, 5, 99]);
+
+        assert_eq!(
+            computer.run_till_halt(),
+            Err(IntcodeError::WriteToImmediate {
+                ip: 0,
+                arg_index: 3
+            })
+        );
+    }
+
+    #[test]
+    fn reads_far_addresses_without_allocating() {
+        let program = vec![109, 1_000_000_000_000, 204, 0, 99];
+
+        let mut computer = Computer::with_empty_input(program.clone());
+        computer.run_till_halt().unwrap();
+        assert_eq!(computer.output(), &vec![0]);
+        assert_eq!(
+            computer.memory_stats().last(),
+            Some(&PageStats {
+                start: 1_000_000_000_000 / PAGE_SIZE * PAGE_SIZE,
+                reads: 1,
+                writes: 0
+            })
+        );
+
+        let mut computer = Computer::with_empty_input(program);
+        computer.set_memory_limit(Some(1 << 20));
+        assert_eq!(
+            computer.run_till_halt(),
+            Err(IntcodeError::MemoryLimitExceeded {
+                ip: 2,
+                address: 1_000_000_000_000
+            })
+        );
+    }
+
+    #[test]
+    fn wraps_or_reports_overflow() {
+        let program = vec![1102, isize::MAX, 2, 5, 99, 0];
+
+        let mut computer = Computer::with_empty_input(program.clone());
+        computer.run_till_halt().unwrap();
+        assert_eq!(computer.read_memory(5), -2);
+
+        let mut computer = Computer::with_empty_input(program);
+        computer.set_overflow_checks(true);
+        assert_eq!(
+            computer.run_till_halt(),
+            Err(IntcodeError::Overflow { ip: 0 })
+        );
+    }
+
+    #[test]
+    fn supports_wider_words() {
+        let program: Vec<i128> = vec![1102, 1 << 40, 1 << 40, 7, 4, 7, 99, 0];
+        let mut computer = Computer::with_io(program, VecDeque::new(), Vec::new());
+        computer.set_overflow_checks(true);
+
+        computer.run_till_halt().unwrap();
+        assert_eq!(computer.output(), &vec![1 << 80]);
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn supports_arbitrary_precision() {
+        use num_bigint::BigInt;
+
+        let big = BigInt::from(1u128 << 100);
+        let program = vec![
+            BigInt::from(1102),
+            big.clone(),
+            big.clone(),
+            BigInt::from(5),
+            BigInt::from(99),
+            BigInt::from(0),
+        ];
+        let mut computer = Computer::with_io(program, VecDeque::new(), Vec::new());
+        computer.set_overflow_checks(true);
+
+        computer.run_till_halt().unwrap();
+        assert_eq!(computer.read_memory(5), &big * &big);
+    }
+
+    #[test]
+    fn runs_code_at_far_addresses() {
+        let far = 1_000_000_000_000;
+        let mut computer = Computer::with_empty_input(vec![1101, 0, 99, far, 1105, 1, far]);
+
+        assert_eq!(computer.run_till_halt(), Ok(()));
+        assert_eq!(computer.ip(), far as usize);
+    }
+
+    #[test]
+    fn reports_write_to_protected_memory() {
+        let mut computer = Computer::with_empty_input(vec![1101, 1, 1, 9, 1101, 1, 1, 5, 99]);
+        computer.set_image_protection(true);
+
+        assert_eq!(
+            computer.run_till_halt(),
+            Err(IntcodeError::WriteToProtectedMemory { ip: 4, address: 5 })
+        );
+    }
+
+    // Outputs the input values multiplied by 2 until a 0 is read
+    const DOUBLER: [isize; 16] = [
+        3, 15, 1006, 15, 14, 1002, 15, 2, 15, 4, 15, 1105, 1, 0, 99, 0,
+    ];
+
+    #[test]
+    fn writes_low_addresses_with_empty_cache() {
+        let mut computer = Computer::with_empty_input(vec![99]);
+        for addr in 0..4 {
+            computer.write_memory(addr, 5).unwrap();
+        }
+        assert_eq!(computer.read_memory(0), 5);
+
+        let mut computer = Computer::new(DOUBLER.to_vec(), vec![1]);
+        let snapshot = computer.snapshot();
+        computer.run().unwrap();
+        computer.restore(&snapshot);
+        computer.write_memory(0, 3).unwrap();
+        assert_eq!(computer.run(), Ok(RunState::Output(2)));
+    }
+
+    #[test]
+    fn snapshot_restores_state() {
+        let mut computer = Computer::with_empty_input(DOUBLER.to_vec());
+        computer.input_mut().push_back(3);
+        assert_eq!(computer.run(), Ok(RunState::Output(6)));
+
+        let snapshot = computer.snapshot();
+        computer.input_mut().push_back(4);
+        assert_eq!(computer.run(), Ok(RunState::Output(8)));
+
+        computer.restore(&snapshot);
+        assert_eq!(computer.output(), &vec![6]);
+        computer.input_mut().push_back(5);
+        assert_eq!(computer.run(), Ok(RunState::Output(10)));
+        assert_eq!(computer.output(), &vec![6, 10]);
+    }
+
+    #[test]
+    fn clones_are_independent() {
+        let mut computer = Computer::new(DOUBLER.to_vec(), vec![1]);
+        computer.run().unwrap();
+
+        let mut clone = computer.clone();
+        clone.input_mut().push_back(2);
+        computer.input_mut().push_back(3);
+
+        assert_eq!(clone.run(), Ok(RunState::Output(4)));
+        assert_eq!(computer.run(), Ok(RunState::Output(6)));
+        assert_eq!(clone.output(), &vec![2, 4]);
+        assert_eq!(computer.output(), &vec![2, 6]);
+    }
+
+    #[test]
+    fn snapshot_can_be_serialized() {
+        let mut computer = Computer::new(DOUBLER.to_vec(), vec![1, 2]);
+        computer.run().unwrap();
+
+        let json = serde_json::to_string(&computer.snapshot()).unwrap();
+        let snapshot: Snapshot = serde_json::from_str(&json).unwrap();
+        let mut resumed = Computer::from_snapshot(snapshot);
+
+        assert_eq!(resumed.run(), Ok(RunState::Output(4)));
+        assert_eq!(resumed.run(), Ok(RunState::AwaitingInput));
+        assert_eq!(resumed.output(), &vec![2, 4]);
+    }
+
+    #[test]
+    fn self_modifying_code_invalidates_cached_instructions() {
+        // Outputs 5, then changes the operand of the output instruction to 7 and runs it again
+        let program = vec![
+            104, 5, 1101, 0, 7, 1, 1005, 17, 16, 1101, 0, 1, 17, 1105, 1, 0, 99, 0,
+        ];
+
+        let mut computer = Computer::with_empty_input(program.clone());
+        computer.run_till_halt().unwrap();
+        assert_eq!(computer.output(), &vec![5, 7]);
+
+        let mut computer = Computer::with_empty_input(program);
+        computer.set_instruction_cache(false);
+        computer.run_till_halt().unwrap();
+        assert_eq!(computer.output(), &vec![5, 7]);
+    }
+
+    #[test]
+    fn steps_back_to_the_initial_state() {
+        let mut computer = Computer::new(DOUBLER.to_vec(), vec![3, 4, 0]);
+        computer.set_journal(Some(1000));
+        let initial = computer.snapshot();
+        computer.run_till_halt().unwrap();
+        assert_eq!(computer.output(), &vec![6, 8]);
+
+        while computer.step_back() {}
+        assert_eq!(computer.snapshot(), initial);
+
+        computer.run_till_halt().unwrap();
+        assert_eq!(computer.output(), &vec![6, 8]);
+    }
+
+    #[test]
+    fn journal_keeps_the_latest_steps() {
+        let mut computer = Computer::new(DOUBLER.to_vec(), vec![3, 0]);
+        assert!(!computer.step_back());
+
+        computer.set_journal(Some(3));
+        computer.run_till_halt().unwrap();
+        assert_eq!(computer.journal_len(), 3);
+
+        assert!(computer.step_back());
+        assert!(computer.step_back());
+        assert!(computer.step_back());
+        assert!(!computer.step_back());
+        assert_eq!(computer.ip(), 11);
+    }
+
+    #[test]
+    fn runs_back_to_the_last_input() {
+        let mut computer = Computer::new(DOUBLER.to_vec(), vec![3, 4, 0]);
+        computer.set_journal(Some(100));
+        computer.run_till_halt().unwrap();
+
+        let is_reading = |c: &Computer| c.read_memory(c.ip()) % 100 == 3;
+        assert!(computer.run_back_until(is_reading));
+        assert_eq!(computer.input(), &VecDeque::from(vec![0]));
+
+        // Replay with a different input from that point
+        computer.input_mut().clear();
+        computer.input_mut().extend(&[5, 0]);
+        computer.run_till_halt().unwrap();
+        assert_eq!(computer.output(), &vec![6, 8, 10]);
+    }
 }
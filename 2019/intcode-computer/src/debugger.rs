@@ -0,0 +1,268 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use crate::{
+    instruction::{InstructionHeader, Opcode},
+    Computer, IntcodeError, RunState,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Stops before executing the instruction at the address.
+    Address(usize),
+    /// Stops before executing an instruction with the opcode.
+    Opcode(Opcode),
+    /// Stops after the value is written to the output.
+    Output(isize),
+}
+
+impl Display for Breakpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Breakpoint::Address(addr) => write!(f, "address {}", addr),
+            Breakpoint::Opcode(opcode) => write!(f, "opcode {}", opcode.mnemonic()),
+            Breakpoint::Output(value) => write!(f, "output {}", value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    /// A single step finished without hitting anything else.
+    Stepped,
+    Breakpoint(Breakpoint),
+    Watchpoint {
+        addr: usize,
+        old: isize,
+        new: isize,
+    },
+    AwaitingInput,
+    Halted,
+    Error(IntcodeError),
+}
+
+impl Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StopReason::Stepped => write!(f, "stepped"),
+            StopReason::Breakpoint(breakpoint) => write!(f, "breakpoint on {}", breakpoint),
+            StopReason::Watchpoint { addr, old, new } => {
+                write!(f, "watchpoint: [{}] changed from {} to {}", addr, old, new)
+            }
+            StopReason::AwaitingInput => write!(f, "awaiting input"),
+            StopReason::Halted => write!(f, "halted"),
+            StopReason::Error(e) => write!(f, "error: {}", e),
+        }
+    }
+}
+
+/// Runs a `Computer` one instruction at a time, stopping on breakpoints and on changes to watched
+/// memory cells.
+pub struct Debugger {
+    pub computer: Computer,
+    breakpoints: Vec<Breakpoint>,
+    /// Watched addresses with their last seen values.
+    watchpoints: BTreeMap<usize, isize>,
+}
+
+impl Debugger {
+    pub fn new(computer: Computer) -> Self {
+        Self {
+            computer,
+            breakpoints: Vec::new(),
+            watchpoints: BTreeMap::new(),
+        }
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    pub fn remove_breakpoint(&mut self, index: usize) -> Option<Breakpoint> {
+        if index < self.breakpoints.len() {
+            Some(self.breakpoints.remove(index))
+        } else {
+            None
+        }
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.watchpoints.keys().cloned()
+    }
+
+    pub fn watch(&mut self, addr: usize) {
+        let value = self.computer.read_memory(addr);
+        self.watchpoints.insert(addr, value);
+    }
+
+    pub fn unwatch(&mut self, addr: usize) -> bool {
+        self.watchpoints.remove(&addr).is_some()
+    }
+
+    /// Executes a single instruction, ignoring breakpoints at the current address.
+    pub fn step(&mut self) -> StopReason {
+        let state = match self.computer.step() {
+            Ok(state) => state,
+            Err(e) => return StopReason::Error(e),
+        };
+
+        if let Some(reason) = self.check_watchpoints() {
+            return reason;
+        }
+
+        match state {
+            None => StopReason::Stepped,
+            Some(RunState::Halted) => StopReason::Halted,
+            Some(RunState::AwaitingInput) => StopReason::AwaitingInput,
+            Some(RunState::Output(value)) => {
+                if self.breakpoints.contains(&Breakpoint::Output(value)) {
+                    StopReason::Breakpoint(Breakpoint::Output(value))
+                } else {
+                    StopReason::Stepped
+                }
+            }
+        }
+    }
+
+    /// Runs until a breakpoint or watchpoint is hit, or the program stops. Breakpoints at the
+    /// current address are ignored, so that execution can be resumed after hitting one.
+    pub fn resume(&mut self) -> StopReason {
+        loop {
+            match self.step() {
+                StopReason::Stepped => {}
+                reason => return reason,
+            }
+
+            if let Some(breakpoint) = self.breakpoint_before_instruction() {
+                return StopReason::Breakpoint(breakpoint);
+            }
+        }
+    }
+
+    fn breakpoint_before_instruction(&self) -> Option<Breakpoint> {
+        let ip = self.computer.ip();
+        let opcode = InstructionHeader::parse(self.computer.read_memory(ip))
+            .ok()
+            .map(|h| h.opcode);
+
+        self.breakpoints.iter().cloned().find(|b| match *b {
+            Breakpoint::Address(addr) => addr == ip,
+            Breakpoint::Opcode(o) => Some(o) == opcode,
+            Breakpoint::Output(_) => false,
+        })
+    }
+
+    fn check_watchpoints(&mut self) -> Option<StopReason> {
+        let computer = &self.computer;
+
+        self.watchpoints.iter_mut().find_map(|(&addr, old)| {
+            let new = computer.read_memory(addr);
+            if new == *old {
+                return None;
+            }
+
+            let reason = StopReason::Watchpoint {
+                addr,
+                old: *old,
+                new,
+            };
+            *old = new;
+
+            Some(reason)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Counts down from the input value, outputting each value
+    const COUNTDOWN: [isize; 14] = [3, 13, 4, 13, 1001, 13, -1, 13, 1005, 13, 2, 99, 0, 0];
+
+    fn debugger(input: Vec<isize>) -> Debugger {
+        Debugger::new(Computer::new(COUNTDOWN.to_vec(), input))
+    }
+
+    #[test]
+    fn stops_on_address_breakpoints() {
+        let mut debugger = debugger(vec![3]);
+        debugger.add_breakpoint(Breakpoint::Address(4));
+
+        assert_eq!(
+            debugger.resume(),
+            StopReason::Breakpoint(Breakpoint::Address(4))
+        );
+        assert_eq!(debugger.computer.output(), &vec![3]);
+
+        assert_eq!(
+            debugger.resume(),
+            StopReason::Breakpoint(Breakpoint::Address(4))
+        );
+        assert_eq!(debugger.computer.output(), &vec![3, 2]);
+
+        debugger.remove_breakpoint(0);
+        assert_eq!(debugger.resume(), StopReason::Halted);
+        assert_eq!(debugger.computer.output(), &vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn stops_on_opcode_and_output_breakpoints() {
+        let mut debugger = debugger(vec![3]);
+        debugger.add_breakpoint(Breakpoint::Opcode(Opcode::JumpIfTrue));
+        debugger.add_breakpoint(Breakpoint::Output(1));
+
+        assert_eq!(
+            debugger.resume(),
+            StopReason::Breakpoint(Breakpoint::Opcode(Opcode::JumpIfTrue))
+        );
+        assert_eq!(debugger.computer.ip(), 8);
+
+        debugger.remove_breakpoint(0);
+        assert_eq!(
+            debugger.resume(),
+            StopReason::Breakpoint(Breakpoint::Output(1))
+        );
+        assert_eq!(debugger.computer.output(), &vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn stops_on_watchpoints() {
+        let mut debugger = debugger(vec![2]);
+        debugger.watch(13);
+
+        assert_eq!(
+            debugger.resume(),
+            StopReason::Watchpoint {
+                addr: 13,
+                old: 0,
+                new: 2
+            }
+        );
+        assert_eq!(
+            debugger.resume(),
+            StopReason::Watchpoint {
+                addr: 13,
+                old: 2,
+                new: 1
+            }
+        );
+    }
+
+    #[test]
+    fn steps_single_instructions() {
+        let mut debugger = debugger(vec![]);
+
+        assert_eq!(debugger.step(), StopReason::AwaitingInput);
+        assert_eq!(debugger.computer.ip(), 0);
+
+        debugger.computer.input_mut().push_back(1);
+        assert_eq!(debugger.step(), StopReason::Stepped);
+        assert_eq!(debugger.computer.ip(), 2);
+    }
+}
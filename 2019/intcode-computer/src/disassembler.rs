@@ -0,0 +1,291 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::instruction::{ArgMode, InstructionHeader, Opcode};
+
+const DATA_VALUES_PER_LINE: usize = 8;
+
+/// Turns a program into a textual listing with one instruction per line.
+///
+/// Only instructions reachable from address 0 are decoded. Both branches of every jump are
+/// followed, but only jumps with an immediate destination can be resolved, so code reached only
+/// through indirect jumps is shown as `.data`. Immediate jump destinations get `L<address>`
+/// labels.
+pub fn disassemble(program: &[isize]) -> String {
+    let mut instructions = find_reachable_instructions(program);
+    // Non-canonical headers are kept as data so that the listing assembles back into the exact
+    // same program
+    instructions.retain(|&addr, header| header.value() == program[addr]);
+    let jump_targets: BTreeSet<usize> = instructions
+        .iter()
+        .filter_map(|(&addr, header)| immediate_jump_target(program, addr, header))
+        .collect();
+
+    let lines = layout(program, &instructions, &jump_targets);
+    let line_starts: BTreeSet<usize> = lines.iter().map(Line::addr).collect();
+    let labels: BTreeSet<usize> = jump_targets.intersection(&line_starts).cloned().collect();
+
+    let mut listing = String::new();
+    for line in lines {
+        if labels.contains(&line.addr()) {
+            listing.push_str(&format!("{}:\n", label_name(line.addr())));
+        }
+
+        let body = match line {
+            Line::Instruction { addr, header } => {
+                format_instruction(program, addr, &header, &labels)
+            }
+            Line::Data { addr, len } => format!(
+                ".data {}",
+                program[addr..addr + len]
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+        listing.push_str(&format!("{:>6}: {}\n", line.addr(), body));
+    }
+
+    listing
+}
+
+/// Formats the instruction at the start of `cells`, or returns `None` if the cells do not start
+/// with a valid instruction.
+pub fn disassemble_instruction(cells: &[isize]) -> Option<String> {
+    let header = InstructionHeader::parse(*cells.first()?).ok()?;
+    if cells.len() < header.opcode.instruction_len() {
+        return None;
+    }
+
+    Some(format_instruction(cells, 0, &header, &BTreeSet::new()))
+}
+
+fn label_name(addr: usize) -> String {
+    format!("L{}", addr)
+}
+
+enum Line {
+    Instruction {
+        addr: usize,
+        header: InstructionHeader,
+    },
+    Data {
+        addr: usize,
+        len: usize,
+    },
+}
+
+impl Line {
+    fn addr(&self) -> usize {
+        match *self {
+            Line::Instruction { addr, .. } | Line::Data { addr, .. } => addr,
+        }
+    }
+}
+
+/// Instructions reachable from address 0, following both branches of jumps with immediate
+/// destinations.
+pub(crate) fn find_reachable_instructions(program: &[isize]) -> BTreeMap<usize, InstructionHeader> {
+    let mut instructions = BTreeMap::new();
+    let mut pending = vec![0];
+
+    while let Some(addr) = pending.pop() {
+        if addr >= program.len() || instructions.contains_key(&addr) {
+            continue;
+        }
+
+        let header = match InstructionHeader::parse(program[addr]) {
+            Ok(header) => header,
+            Err(_) => continue,
+        };
+        if addr + header.opcode.instruction_len() > program.len() {
+            continue;
+        }
+
+        instructions.insert(addr, header);
+
+        if header.opcode != Opcode::Halt {
+            pending.push(addr + header.opcode.instruction_len());
+        }
+        if let Some(target) = immediate_jump_target(program, addr, &header) {
+            pending.push(target);
+        }
+    }
+
+    instructions
+}
+
+pub(crate) fn immediate_jump_target(
+    program: &[isize],
+    addr: usize,
+    header: &InstructionHeader,
+) -> Option<usize> {
+    match header.opcode {
+        Opcode::JumpIfTrue | Opcode::JumpIfFalse if header.mode(2) == ArgMode::Immediate => {
+            let target = program[addr + 2];
+            if target >= 0 {
+                Some(target as usize)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Splits the program into instructions and runs of data. Instructions that overlap an earlier
+/// instruction are dropped. Data runs are split at jump targets so they can be labelled.
+fn layout(
+    program: &[isize],
+    instructions: &BTreeMap<usize, InstructionHeader>,
+    jump_targets: &BTreeSet<usize>,
+) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut addr = 0;
+
+    while addr < program.len() {
+        if let Some(&header) = instructions.get(&addr) {
+            lines.push(Line::Instruction { addr, header });
+            addr += header.opcode.instruction_len();
+            continue;
+        }
+
+        let start = addr;
+        addr += 1;
+        while addr < program.len()
+            && addr - start < DATA_VALUES_PER_LINE
+            && !instructions.contains_key(&addr)
+            && !jump_targets.contains(&addr)
+        {
+            addr += 1;
+        }
+
+        lines.push(Line::Data {
+            addr: start,
+            len: addr - start,
+        });
+    }
+
+    lines
+}
+
+fn format_instruction(
+    program: &[isize],
+    addr: usize,
+    header: &InstructionHeader,
+    labels: &BTreeSet<usize>,
+) -> String {
+    let args_count = header.opcode.args_count();
+    if args_count == 0 {
+        return header.opcode.mnemonic().to_string();
+    }
+
+    let args = (1..=args_count)
+        .map(|arg_index| {
+            let value = program[addr + arg_index];
+            let is_destination =
+                arg_index == 2 && matches!(header.opcode, Opcode::JumpIfTrue | Opcode::JumpIfFalse);
+
+            match header.mode(arg_index) {
+                ArgMode::Immediate
+                    if is_destination && value >= 0 && labels.contains(&(value as usize)) =>
+                {
+                    label_name(value as usize)
+                }
+                ArgMode::Immediate => format!("#{}", value),
+                ArgMode::Position => format!("[{}]", value),
+                ArgMode::Relative if value < 0 => format!("rb-{}", value.unsigned_abs()),
+                ArgMode::Relative => format!("rb+{}", value),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    format!("{} {}", header.opcode.mnemonic(), args.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_immediate_jump_targets() {
+        let program = vec![3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1];
+
+        assert_eq!(
+            disassemble(&program),
+            "     0: in [3]
+     2: jt #-1, L9
+     5: add #0, #0, [12]
+L9:
+     9: out [12]
+    11: hlt
+    12: .data 1
+"
+        );
+    }
+
+    #[test]
+    fn shows_relative_arguments_and_data() {
+        let program = vec![109, 1, 204, -1, 99, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+
+        assert_eq!(
+            disassemble(&program),
+            "     0: arb #1
+     2: out rb-1
+     4: hlt
+     5: .data 7, 8, 9, 10, 11, 12, 13, 14
+    13: .data 15
+"
+        );
+    }
+
+    #[test]
+    fn shows_smallest_relative_offset() {
+        assert_eq!(
+            disassemble(&[204, isize::MIN, 99]),
+            format!(
+                "     0: out rb-{}\n     2: hlt\n",
+                isize::MIN.unsigned_abs()
+            )
+        );
+    }
+
+    #[test]
+    fn shows_unreachable_and_invalid_code_as_data() {
+        let program = vec![1106, 0, 4, 42, 99, 1, 0, 0, 0];
+
+        assert_eq!(
+            disassemble(&program),
+            "     0: jf #0, L4
+     3: .data 42
+L4:
+     4: hlt
+     5: .data 1, 0, 0, 0
+"
+        );
+    }
+
+    #[test]
+    fn shows_non_canonical_headers_as_data() {
+        // The mode of the unused second argument makes 11104 a non-canonical `out`
+        let program = vec![11104, 5, 104, 6, 99];
+
+        assert_eq!(
+            disassemble(&program),
+            "     0: .data 11104, 5
+     2: out #6
+     4: hlt
+"
+        );
+    }
+
+    #[test]
+    fn disassembles_single_instruction() {
+        assert_eq!(
+            disassemble_instruction(&[21101, 3, -2, 0, 99]),
+            Some("add #3, #-2, rb+0".to_string())
+        );
+        assert_eq!(disassemble_instruction(&[1101, 3]), None);
+        assert_eq!(disassemble_instruction(&[42]), None);
+    }
+}
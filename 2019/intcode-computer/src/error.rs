@@ -0,0 +1,102 @@
+use std::{error::Error, fmt::Display};
+
+/// Errors caused by a malformed program or missing input. `ip` is always the address of the
+/// instruction that failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntcodeError {
+    InvalidOpcode {
+        ip: usize,
+        value: isize,
+    },
+    InvalidMode {
+        ip: usize,
+        value: isize,
+        arg_index: usize,
+    },
+    NegativeAddress {
+        ip: usize,
+        address: isize,
+    },
+    InputExhausted {
+        ip: usize,
+    },
+    WriteToImmediate {
+        ip: usize,
+        arg_index: usize,
+    },
+    MemoryLimitExceeded {
+        ip: usize,
+        address: usize,
+    },
+    WriteToProtectedMemory {
+        ip: usize,
+        address: usize,
+    },
+    /// An arithmetic result does not fit in the word type, with overflow checks enabled. Also
+    /// reported for addresses and relative base changes that do not fit in an `isize`.
+    Overflow {
+        ip: usize,
+    },
+}
+
+impl IntcodeError {
+    pub fn ip(&self) -> usize {
+        match *self {
+            Self::InvalidOpcode { ip, .. }
+            | Self::InvalidMode { ip, .. }
+            | Self::NegativeAddress { ip, .. }
+            | Self::InputExhausted { ip }
+            | Self::WriteToImmediate { ip, .. }
+            | Self::MemoryLimitExceeded { ip, .. }
+            | Self::WriteToProtectedMemory { ip, .. }
+            | Self::Overflow { ip } => ip,
+        }
+    }
+}
+
+impl Error for IntcodeError {}
+
+impl Display for IntcodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidOpcode { ip, value } => {
+                write!(f, "invalid opcode in {} at address {}", value, ip)
+            }
+            Self::InvalidMode {
+                ip,
+                value,
+                arg_index,
+            } => write!(
+                f,
+                "invalid mode for argument {} in {} at address {}",
+                arg_index, value, ip
+            ),
+            Self::NegativeAddress { ip, address } => write!(
+                f,
+                "instruction at address {} accesses negative address {}",
+                ip, address
+            ),
+            Self::InputExhausted { ip } => {
+                write!(f, "no input left for instruction at address {}", ip)
+            }
+            Self::WriteToImmediate { ip, arg_index } => write!(
+                f,
+                "argument {} of instruction at address {} is written to, but uses immediate mode",
+                arg_index, ip
+            ),
+            Self::MemoryLimitExceeded { ip, address } => write!(
+                f,
+                "instruction at address {} accesses address {} above the memory limit",
+                ip, address
+            ),
+            Self::WriteToProtectedMemory { ip, address } => write!(
+                f,
+                "instruction at address {} writes to address {} of the protected program",
+                ip, address
+            ),
+            Self::Overflow { ip } => {
+                write!(f, "arithmetic overflow in instruction at address {}", ip)
+            }
+        }
+    }
+}
@@ -0,0 +1,356 @@
+//! Property-based tests comparing `Computer` with a reference interpreter, and checking that
+//! parsing never panics.
+//!
+//! Besides random programs, the puzzle inputs under `2019/*/input.txt` are used as seeds: they
+//! are run with a few random cells changed.
+
+use std::{collections::VecDeque, fs, sync::OnceLock};
+
+use proptest::prelude::*;
+
+use crate::{program, ArgMode, Computer, InstructionHeader, IntcodeError, Opcode, RunState};
+
+/// Memory limit of both interpreters, which keeps the reference one from allocating too much.
+const MEMORY_LIMIT: usize = 1 << 16;
+const MAX_STEPS: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fault {
+    InvalidOpcode,
+    InvalidMode,
+    NegativeAddress,
+    WriteToImmediate,
+    MemoryLimitExceeded,
+    Overflow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Halted,
+    AwaitingInput,
+    Failed {
+        fault: Fault,
+        ip: usize,
+    },
+    /// Still running after `MAX_STEPS` instructions.
+    Running,
+}
+
+/// A deliberately simple interpreter, following the puzzle descriptions step by step with no
+/// decoding tables, caching or paging.
+struct Reference {
+    memory: Vec<isize>,
+    ip: usize,
+    relative_base: isize,
+    input: VecDeque<isize>,
+    output: Vec<isize>,
+}
+
+impl Reference {
+    fn new(program: Vec<isize>, input: Vec<isize>) -> Self {
+        Self {
+            memory: program,
+            ip: 0,
+            relative_base: 0,
+            input: input.into(),
+            output: Vec::new(),
+        }
+    }
+
+    fn run(&mut self) -> Outcome {
+        for _ in 0..MAX_STEPS {
+            let ip = self.ip;
+            match self.step() {
+                Ok(None) => {}
+                Ok(Some(outcome)) => return outcome,
+                Err(fault) => return Outcome::Failed { fault, ip },
+            }
+        }
+
+        Outcome::Running
+    }
+
+    fn read(&self, addr: usize) -> Result<isize, Fault> {
+        if addr >= MEMORY_LIMIT {
+            return Err(Fault::MemoryLimitExceeded);
+        }
+
+        Ok(self.memory.get(addr).cloned().unwrap_or(0))
+    }
+
+    fn write(&mut self, addr: usize, value: isize) -> Result<(), Fault> {
+        if addr >= MEMORY_LIMIT {
+            return Err(Fault::MemoryLimitExceeded);
+        }
+        if addr >= self.memory.len() {
+            self.memory.resize(addr + 1, 0);
+        }
+
+        self.memory[addr] = value;
+        Ok(())
+    }
+
+    fn address(value: isize) -> Result<usize, Fault> {
+        if value < 0 {
+            Err(Fault::NegativeAddress)
+        } else {
+            Ok(value as usize)
+        }
+    }
+
+    fn step(&mut self) -> Result<Option<Outcome>, Fault> {
+        let header = self.read(self.ip)?;
+        if !(0..100_000).contains(&header) {
+            return Err(Fault::InvalidOpcode);
+        }
+        let args_count = match header % 100 {
+            1 | 2 | 7 | 8 => 3,
+            5 | 6 => 2,
+            3 | 4 | 9 => 1,
+            99 => 0,
+            _ => return Err(Fault::InvalidOpcode),
+        };
+
+        let mut modes = Vec::new();
+        let mut digits = header / 100;
+        for _ in 0..args_count {
+            if digits % 10 > 2 {
+                return Err(Fault::InvalidMode);
+            }
+            modes.push(digits % 10);
+            digits /= 10;
+        }
+
+        let mut raw = Vec::new();
+        for i in 0..args_count {
+            raw.push(self.read(self.ip + i + 1)?);
+        }
+
+        // Address of the cell an argument refers to
+        let cell = |this: &Self, i: usize| -> Result<usize, Fault> {
+            match modes[i] {
+                0 => Self::address(raw[i]),
+                2 => Self::address(
+                    raw[i]
+                        .checked_add(this.relative_base)
+                        .ok_or(Fault::Overflow)?,
+                ),
+                _ => Err(Fault::WriteToImmediate),
+            }
+        };
+        let value = |this: &Self, i: usize| -> Result<isize, Fault> {
+            match modes[i] {
+                1 => Ok(raw[i]),
+                _ => this.read(cell(this, i)?),
+            }
+        };
+
+        let next_ip = self.ip + args_count + 1;
+        match header % 100 {
+            1 | 2 | 7 | 8 => {
+                let a = value(self, 0)?;
+                let b = value(self, 1)?;
+                let out = cell(self, 2)?;
+                let result = match header % 100 {
+                    1 => a.wrapping_add(b),
+                    2 => a.wrapping_mul(b),
+                    7 => (a < b) as isize,
+                    _ => (a == b) as isize,
+                };
+                self.write(out, result)?;
+            }
+            3 => {
+                let to = cell(self, 0)?;
+                match self.input.pop_front() {
+                    Some(v) => self.write(to, v)?,
+                    None => return Ok(Some(Outcome::AwaitingInput)),
+                }
+            }
+            4 => {
+                let v = value(self, 0)?;
+                self.output.push(v);
+            }
+            5 | 6 => {
+                let condition = value(self, 0)?;
+                let destination = Self::address(value(self, 1)?)?;
+                if (condition != 0) == (header % 100 == 5) {
+                    self.ip = destination;
+                    return Ok(None);
+                }
+            }
+            9 => {
+                let change = value(self, 0)?;
+                self.relative_base = self.relative_base.wrapping_add(change);
+            }
+            _ => return Ok(Some(Outcome::Halted)),
+        }
+
+        self.ip = next_ip;
+        Ok(None)
+    }
+}
+
+fn fault(error: &IntcodeError) -> Fault {
+    match error {
+        IntcodeError::InvalidOpcode { .. } => Fault::InvalidOpcode,
+        IntcodeError::InvalidMode { .. } => Fault::InvalidMode,
+        IntcodeError::NegativeAddress { .. } => Fault::NegativeAddress,
+        IntcodeError::WriteToImmediate { .. } => Fault::WriteToImmediate,
+        IntcodeError::MemoryLimitExceeded { .. } => Fault::MemoryLimitExceeded,
+        IntcodeError::Overflow { .. } => Fault::Overflow,
+        error => panic!("unexpected error {:?}", error),
+    }
+}
+
+fn run_computer(program: &[isize], input: &[isize], cached: bool) -> (Outcome, Vec<isize>) {
+    let mut computer = Computer::new(program.to_vec(), input.to_vec());
+    computer.set_memory_limit(Some(MEMORY_LIMIT));
+    computer.set_instruction_cache(cached);
+
+    for _ in 0..MAX_STEPS {
+        let ip = computer.ip();
+        let outcome = match computer.step() {
+            Ok(Some(RunState::Halted)) => Outcome::Halted,
+            Ok(Some(RunState::AwaitingInput)) => Outcome::AwaitingInput,
+            Ok(_) => continue,
+            Err(error) => {
+                assert_eq!(error.ip(), ip, "error reported at a different address");
+                Outcome::Failed {
+                    fault: fault(&error),
+                    ip,
+                }
+            }
+        };
+
+        return (outcome, computer.output().clone());
+    }
+
+    (Outcome::Running, computer.output().clone())
+}
+
+fn assert_matches_reference(program: &[isize], input: &[isize]) -> Result<(), TestCaseError> {
+    let mut reference = Reference::new(program.to_vec(), input.to_vec());
+    let expected = (reference.run(), reference.output);
+
+    prop_assert_eq!(&run_computer(program, input, true), &expected);
+    prop_assert_eq!(&run_computer(program, input, false), &expected);
+
+    Ok(())
+}
+
+/// An instruction with a valid header. Arguments that are written to never use immediate mode.
+fn instruction() -> impl Strategy<Value = Vec<isize>> {
+    (
+        prop::sample::select(Opcode::ALL.to_vec()),
+        prop::collection::vec((0..3isize, 0..1000isize), 3),
+    )
+        .prop_map(|(opcode, args)| {
+            let written = match opcode {
+                Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => Some(3),
+                Opcode::ReadInput => Some(1),
+                _ => None,
+            };
+            let args = &args[..opcode.args_count()];
+
+            let modes: Vec<ArgMode> = args
+                .iter()
+                .enumerate()
+                .map(|(i, &(mode, _))| match ArgMode::from_digit(mode).unwrap() {
+                    ArgMode::Immediate if written == Some(i + 1) => ArgMode::Position,
+                    mode => mode,
+                })
+                .collect();
+
+            let mut cells = vec![InstructionHeader::new(opcode, &modes).value()];
+            cells.extend(modes.iter().zip(args).map(|(mode, &(_, raw))| match mode {
+                ArgMode::Position => raw % 80,
+                ArgMode::Relative => raw % 20 - 10,
+                ArgMode::Immediate => raw % 150 - 50,
+            }));
+
+            cells
+        })
+}
+
+fn well_formed_program() -> impl Strategy<Value = Vec<isize>> {
+    (
+        prop::collection::vec(instruction(), 1..30),
+        prop::collection::vec(-50..100isize, 0..16),
+    )
+        .prop_map(|(instructions, data)| {
+            instructions
+                .into_iter()
+                .flatten()
+                .chain(data)
+                .collect::<Vec<isize>>()
+        })
+}
+
+/// Programs from the puzzle inputs of 2019.
+fn puzzle_programs() -> &'static Vec<Vec<isize>> {
+    static PROGRAMS: OnceLock<Vec<Vec<isize>>> = OnceLock::new();
+
+    PROGRAMS.get_or_init(|| {
+        let mut paths: Vec<_> = fs::read_dir(format!("{}/..", env!("CARGO_MANIFEST_DIR")))
+            .unwrap()
+            .map(|entry| entry.unwrap().path().join("input.txt"))
+            .filter(|path| path.exists())
+            .collect();
+        paths.sort();
+
+        paths
+            .iter()
+            .filter_map(|path| program::parse_from_string(&fs::read_to_string(path).unwrap()).ok())
+            .collect()
+    })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(512))]
+
+    #[test]
+    fn random_programs_match_reference(
+        program in well_formed_program(),
+        input in prop::collection::vec(-50..100isize, 0..8),
+    ) {
+        assert_matches_reference(&program, &input)?;
+    }
+
+    #[test]
+    fn mutated_puzzles_match_reference(
+        seed in any::<prop::sample::Index>(),
+        mutations in prop::collection::vec((any::<prop::sample::Index>(), -10..1000isize), 0..4),
+        input in prop::collection::vec(-5..100isize, 0..16),
+    ) {
+        let mut program = seed.get(puzzle_programs()).clone();
+        for (index, value) in mutations {
+            let len = program.len();
+            program[index.index(len)] = value;
+        }
+
+        assert_matches_reference(&program, &input)?;
+    }
+
+    #[test]
+    fn decoding_arbitrary_memory_does_not_panic(
+        memory in prop::collection::vec(any::<isize>(), 1..12),
+        input in prop::collection::vec(any::<isize>(), 0..4),
+    ) {
+        let mut computer = Computer::new(memory.clone(), input.clone());
+        let _ = computer.parse_instruction();
+
+        assert_matches_reference(&memory, &input)?;
+    }
+
+    #[test]
+    fn parsing_arbitrary_text_does_not_panic(text in ".*") {
+        let _ = program::parse_from_string(&text);
+    }
+
+    #[test]
+    fn parsing_round_trips(values in prop::collection::vec(any::<isize>(), 1..20)) {
+        let text = values.iter().map(isize::to_string).collect::<Vec<_>>().join(",");
+
+        prop_assert_eq!(program::parse_from_string(&text).ok(), Some(values));
+    }
+}
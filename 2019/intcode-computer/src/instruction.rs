@@ -1,4 +1,6 @@
-#[derive(PartialEq)]
+use crate::word::Word;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ArgMode {
     Position,
     Immediate,
@@ -6,54 +8,472 @@ pub enum ArgMode {
 }
 
 impl ArgMode {
-    pub fn parse(c: char) -> Self {
-        match c {
-            '0' => ArgMode::Position,
-            '1' => ArgMode::Immediate,
-            '2' => ArgMode::Relative,
-            _ => panic!("Invalid arg mode {}", c),
-        }
-    }
-}
-
-#[derive(PartialEq, Debug)]
-pub enum Instruction {
-    Add {
-        arg1: isize,
-        arg2: isize,
-        out: usize,
-    },
-    Multiply {
-        arg1: isize,
-        arg2: isize,
-        out: usize,
-    },
-    ReadInput {
-        to: usize,
-    },
-    WriteOutput {
-        val: isize,
-    },
-    JumpIfTrue {
-        arg: isize,
-        destination: usize,
-    },
-    JumpIfFalse {
-        arg: isize,
-        destination: usize,
-    },
-    LessThan {
-        arg1: isize,
-        arg2: isize,
-        out: usize,
-    },
-    Equals {
-        arg1: isize,
-        arg2: isize,
-        out: usize,
-    },
-    AdjustRelativeBase {
-        change: isize,
-    },
+    pub fn from_digit(digit: isize) -> Option<Self> {
+        match digit {
+            0 => Some(ArgMode::Position),
+            1 => Some(ArgMode::Immediate),
+            2 => Some(ArgMode::Relative),
+            _ => None,
+        }
+    }
+
+    pub fn digit(self) -> isize {
+        match self {
+            ArgMode::Position => 0,
+            ArgMode::Immediate => 1,
+            ArgMode::Relative => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Opcode {
+    Add,
+    Multiply,
+    ReadInput,
+    WriteOutput,
+    JumpIfTrue,
+    JumpIfFalse,
+    LessThan,
+    Equals,
+    AdjustRelativeBase,
     Halt,
 }
+
+impl Opcode {
+    pub const ALL: [Opcode; 10] = [
+        Opcode::Add,
+        Opcode::Multiply,
+        Opcode::ReadInput,
+        Opcode::WriteOutput,
+        Opcode::JumpIfTrue,
+        Opcode::JumpIfFalse,
+        Opcode::LessThan,
+        Opcode::Equals,
+        Opcode::AdjustRelativeBase,
+        Opcode::Halt,
+    ];
+
+    pub fn from_code(code: isize) -> Option<Self> {
+        match code {
+            1 => Some(Opcode::Add),
+            2 => Some(Opcode::Multiply),
+            3 => Some(Opcode::ReadInput),
+            4 => Some(Opcode::WriteOutput),
+            5 => Some(Opcode::JumpIfTrue),
+            6 => Some(Opcode::JumpIfFalse),
+            7 => Some(Opcode::LessThan),
+            8 => Some(Opcode::Equals),
+            9 => Some(Opcode::AdjustRelativeBase),
+            99 => Some(Opcode::Halt),
+            _ => None,
+        }
+    }
+
+    pub fn code(self) -> isize {
+        match self {
+            Opcode::Add => 1,
+            Opcode::Multiply => 2,
+            Opcode::ReadInput => 3,
+            Opcode::WriteOutput => 4,
+            Opcode::JumpIfTrue => 5,
+            Opcode::JumpIfFalse => 6,
+            Opcode::LessThan => 7,
+            Opcode::Equals => 8,
+            Opcode::AdjustRelativeBase => 9,
+            Opcode::Halt => 99,
+        }
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Self> {
+        Self::ALL.iter().cloned().find(|o| o.mnemonic() == mnemonic)
+    }
+
+    pub fn args_count(self) -> usize {
+        match self {
+            Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => 3,
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => 2,
+            Opcode::ReadInput | Opcode::WriteOutput | Opcode::AdjustRelativeBase => 1,
+            Opcode::Halt => 0,
+        }
+    }
+
+    /// Number of memory cells taken by the instruction, including the header.
+    pub fn instruction_len(self) -> usize {
+        self.args_count() + 1
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Opcode::Add => "add",
+            Opcode::Multiply => "mul",
+            Opcode::ReadInput => "in",
+            Opcode::WriteOutput => "out",
+            Opcode::JumpIfTrue => "jt",
+            Opcode::JumpIfFalse => "jf",
+            Opcode::LessThan => "lt",
+            Opcode::Equals => "eq",
+            Opcode::AdjustRelativeBase => "arb",
+            Opcode::Halt => "hlt",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderError {
+    InvalidOpcode,
+    InvalidMode { arg_index: usize },
+}
+
+/// The first memory cell of an instruction: its opcode and the modes of its arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstructionHeader {
+    pub opcode: Opcode,
+    modes: [ArgMode; 3],
+}
+
+impl InstructionHeader {
+    /// Creates a header with one mode for each argument of `opcode`.
+    pub fn new(opcode: Opcode, modes: &[ArgMode]) -> Self {
+        assert_eq!(modes.len(), opcode.args_count(), "invalid number of modes");
+
+        let mut header = Self {
+            opcode,
+            modes: [ArgMode::Position; 3],
+        };
+        header.modes[..modes.len()].copy_from_slice(modes);
+
+        header
+    }
+
+    pub fn parse(value: isize) -> Result<Self, HeaderError> {
+        if !(0..100_000).contains(&value) {
+            return Err(HeaderError::InvalidOpcode);
+        }
+        let opcode = Opcode::from_code(value % 100).ok_or(HeaderError::InvalidOpcode)?;
+
+        let mut modes = [ArgMode::Position; 3];
+        let mut mode_digits = value / 100;
+        for (i, mode) in modes.iter_mut().take(opcode.args_count()).enumerate() {
+            *mode = ArgMode::from_digit(mode_digits % 10)
+                .ok_or(HeaderError::InvalidMode { arg_index: i + 1 })?;
+            mode_digits /= 10;
+        }
+
+        Ok(Self { opcode, modes })
+    }
+
+    /// Same as `parse`, but matches the digits of the value formatted as a string, as the
+    /// computer originally did. Only kept as a baseline for the benchmarks.
+    #[doc(hidden)]
+    pub fn parse_digits(value: isize) -> Result<Self, HeaderError> {
+        let digits = format!("{:0>5}", value.to_string());
+        let opcode = match digits.get(3..) {
+            Some("01") => Opcode::Add,
+            Some("02") => Opcode::Multiply,
+            Some("03") => Opcode::ReadInput,
+            Some("04") => Opcode::WriteOutput,
+            Some("05") => Opcode::JumpIfTrue,
+            Some("06") => Opcode::JumpIfFalse,
+            Some("07") => Opcode::LessThan,
+            Some("08") => Opcode::Equals,
+            Some("09") => Opcode::AdjustRelativeBase,
+            Some("99") => Opcode::Halt,
+            _ => return Err(HeaderError::InvalidOpcode),
+        };
+
+        let mut modes = [ArgMode::Position; 3];
+        for arg_index in 1..=opcode.args_count() {
+            modes[arg_index - 1] = match digits.as_bytes()[3 - arg_index] {
+                b'0' => ArgMode::Position,
+                b'1' => ArgMode::Immediate,
+                b'2' => ArgMode::Relative,
+                _ => return Err(HeaderError::InvalidMode { arg_index }),
+            };
+        }
+
+        Ok(Self { opcode, modes })
+    }
+
+    /// The canonical memory value of the header. Parsing ignores modes of unused arguments, so
+    /// this is not always the value the header was parsed from.
+    pub fn value(&self) -> isize {
+        let modes = self
+            .modes
+            .iter()
+            .rev()
+            .fold(0, |acc, mode| acc * 10 + mode.digit());
+
+        modes * 100 + self.opcode.code()
+    }
+
+    /// Mode of the argument with the given 1-based index.
+    pub fn mode(&self, arg_index: usize) -> ArgMode {
+        self.modes[arg_index - 1]
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Instruction<W = isize> {
+    Add { arg1: W, arg2: W, out: usize },
+    Multiply { arg1: W, arg2: W, out: usize },
+    ReadInput { to: usize },
+    WriteOutput { val: W },
+    JumpIfTrue { arg: W, destination: usize },
+    JumpIfFalse { arg: W, destination: usize },
+    LessThan { arg1: W, arg2: W, out: usize },
+    Equals { arg1: W, arg2: W, out: usize },
+    AdjustRelativeBase { change: W },
+    Halt,
+}
+
+impl<W: Word> Instruction<W> {
+    pub fn opcode(&self) -> Opcode {
+        match self {
+            Instruction::Add { .. } => Opcode::Add,
+            Instruction::Multiply { .. } => Opcode::Multiply,
+            Instruction::ReadInput { .. } => Opcode::ReadInput,
+            Instruction::WriteOutput { .. } => Opcode::WriteOutput,
+            Instruction::JumpIfTrue { .. } => Opcode::JumpIfTrue,
+            Instruction::JumpIfFalse { .. } => Opcode::JumpIfFalse,
+            Instruction::LessThan { .. } => Opcode::LessThan,
+            Instruction::Equals { .. } => Opcode::Equals,
+            Instruction::AdjustRelativeBase { .. } => Opcode::AdjustRelativeBase,
+            Instruction::Halt => Opcode::Halt,
+        }
+    }
+
+    /// Address of the memory cell the instruction writes to, if any.
+    pub fn written_address(&self) -> Option<usize> {
+        match *self {
+            Instruction::Add { out, .. }
+            | Instruction::Multiply { out, .. }
+            | Instruction::LessThan { out, .. }
+            | Instruction::Equals { out, .. } => Some(out),
+            Instruction::ReadInput { to } => Some(to),
+            _ => None,
+        }
+    }
+
+    /// Address of the instruction executed after this one, if this one is at `ip`.
+    pub fn next_ip(&self, ip: usize) -> usize {
+        match self {
+            Instruction::JumpIfTrue { arg, destination } if !arg.is_zero() => *destination,
+            Instruction::JumpIfFalse { arg, destination } if arg.is_zero() => *destination,
+            _ => ip + self.opcode().instruction_len(),
+        }
+    }
+
+    /// Converts the argument values to another type.
+    pub fn map<V>(&self, f: impl Fn(&W) -> V) -> Instruction<V> {
+        match self {
+            Instruction::Add { arg1, arg2, out } => Instruction::Add {
+                arg1: f(arg1),
+                arg2: f(arg2),
+                out: *out,
+            },
+            Instruction::Multiply { arg1, arg2, out } => Instruction::Multiply {
+                arg1: f(arg1),
+                arg2: f(arg2),
+                out: *out,
+            },
+            Instruction::ReadInput { to } => Instruction::ReadInput { to: *to },
+            Instruction::WriteOutput { val } => Instruction::WriteOutput { val: f(val) },
+            Instruction::JumpIfTrue { arg, destination } => Instruction::JumpIfTrue {
+                arg: f(arg),
+                destination: *destination,
+            },
+            Instruction::JumpIfFalse { arg, destination } => Instruction::JumpIfFalse {
+                arg: f(arg),
+                destination: *destination,
+            },
+            Instruction::LessThan { arg1, arg2, out } => Instruction::LessThan {
+                arg1: f(arg1),
+                arg2: f(arg2),
+                out: *out,
+            },
+            Instruction::Equals { arg1, arg2, out } => Instruction::Equals {
+                arg1: f(arg1),
+                arg2: f(arg2),
+                out: *out,
+            },
+            Instruction::AdjustRelativeBase { change } => {
+                Instruction::AdjustRelativeBase { change: f(change) }
+            }
+            Instruction::Halt => Instruction::Halt,
+        }
+    }
+}
+
+impl Instruction {
+    /// The resolved argument values, in the order they appear in memory.
+    pub fn args(&self) -> Vec<isize> {
+        match *self {
+            Instruction::Add { arg1, arg2, out }
+            | Instruction::Multiply { arg1, arg2, out }
+            | Instruction::LessThan { arg1, arg2, out }
+            | Instruction::Equals { arg1, arg2, out } => vec![arg1, arg2, out as isize],
+            Instruction::ReadInput { to } => vec![to as isize],
+            Instruction::WriteOutput { val } => vec![val],
+            Instruction::JumpIfTrue { arg, destination }
+            | Instruction::JumpIfFalse { arg, destination } => vec![arg, destination as isize],
+            Instruction::AdjustRelativeBase { change } => vec![change],
+            Instruction::Halt => vec![],
+        }
+    }
+
+    /// The inverse of `opcode` and `args`. Returns `None` if the number of arguments does not
+    /// match the opcode or an address is negative.
+    pub fn from_parts(opcode: Opcode, args: &[isize]) -> Option<Self> {
+        if args.len() != opcode.args_count() {
+            return None;
+        }
+        let addr = |i: usize| {
+            if args[i] < 0 {
+                None
+            } else {
+                Some(args[i] as usize)
+            }
+        };
+
+        Some(match opcode {
+            Opcode::Add => Instruction::Add {
+                arg1: args[0],
+                arg2: args[1],
+                out: addr(2)?,
+            },
+            Opcode::Multiply => Instruction::Multiply {
+                arg1: args[0],
+                arg2: args[1],
+                out: addr(2)?,
+            },
+            Opcode::ReadInput => Instruction::ReadInput { to: addr(0)? },
+            Opcode::WriteOutput => Instruction::WriteOutput { val: args[0] },
+            Opcode::JumpIfTrue => Instruction::JumpIfTrue {
+                arg: args[0],
+                destination: addr(1)?,
+            },
+            Opcode::JumpIfFalse => Instruction::JumpIfFalse {
+                arg: args[0],
+                destination: addr(1)?,
+            },
+            Opcode::LessThan => Instruction::LessThan {
+                arg1: args[0],
+                arg2: args[1],
+                out: addr(2)?,
+            },
+            Opcode::Equals => Instruction::Equals {
+                arg1: args[0],
+                arg2: args[1],
+                out: addr(2)?,
+            },
+            Opcode::AdjustRelativeBase => Instruction::AdjustRelativeBase { change: args[0] },
+            Opcode::Halt => Instruction::Halt,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_header() {
+        let header = InstructionHeader::parse(1002).unwrap();
+
+        assert_eq!(header.opcode, Opcode::Multiply);
+        assert_eq!(header.mode(1), ArgMode::Position);
+        assert_eq!(header.mode(2), ArgMode::Immediate);
+        assert_eq!(header.mode(3), ArgMode::Position);
+
+        let header = InstructionHeader::parse(204).unwrap();
+        assert_eq!(header.opcode, Opcode::WriteOutput);
+        assert_eq!(header.mode(1), ArgMode::Relative);
+    }
+
+    #[test]
+    fn parses_digits_like_values() {
+        for value in -10..100_000 {
+            assert_eq!(
+                InstructionHeader::parse_digits(value),
+                InstructionHeader::parse(value),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_headers() {
+        assert_eq!(
+            InstructionHeader::parse(42),
+            Err(HeaderError::InvalidOpcode)
+        );
+        assert_eq!(
+            InstructionHeader::parse(-1),
+            Err(HeaderError::InvalidOpcode)
+        );
+        assert_eq!(
+            InstructionHeader::parse(301),
+            Err(HeaderError::InvalidMode { arg_index: 1 })
+        );
+        assert_eq!(
+            InstructionHeader::parse(-1101),
+            Err(HeaderError::InvalidOpcode)
+        );
+        assert_eq!(
+            InstructionHeader::parse(100_001),
+            Err(HeaderError::InvalidOpcode)
+        );
+        assert_eq!(
+            InstructionHeader::parse(30001),
+            Err(HeaderError::InvalidMode { arg_index: 3 })
+        );
+        assert_eq!(
+            InstructionHeader::parse(30099),
+            Ok(InstructionHeader {
+                opcode: Opcode::Halt,
+                modes: [ArgMode::Position; 3]
+            }),
+            "modes of unused arguments are ignored"
+        );
+    }
+
+    #[test]
+    fn encodes_header() {
+        let header = InstructionHeader::new(
+            Opcode::LessThan,
+            &[ArgMode::Relative, ArgMode::Immediate, ArgMode::Position],
+        );
+
+        assert_eq!(header.value(), 1207);
+        assert_eq!(InstructionHeader::parse(1207), Ok(header));
+        assert_eq!(InstructionHeader::parse(30099).unwrap().value(), 99);
+    }
+
+    #[test]
+    fn instruction_parts_round_trip() {
+        let instructions = [
+            Instruction::Add {
+                arg1: -1,
+                arg2: 2,
+                out: 3,
+            },
+            Instruction::ReadInput { to: 7 },
+            Instruction::JumpIfFalse {
+                arg: 0,
+                destination: 12,
+            },
+            Instruction::Halt,
+        ];
+
+        for instr in instructions.iter() {
+            assert_eq!(
+                Instruction::from_parts(instr.opcode(), &instr.args()),
+                Some(*instr)
+            );
+        }
+        assert_eq!(Instruction::from_parts(Opcode::ReadInput, &[-1]), None);
+        assert_eq!(Instruction::from_parts(Opcode::Halt, &[1]), None);
+    }
+}
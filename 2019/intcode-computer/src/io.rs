@@ -0,0 +1,159 @@
+use std::{
+    collections::VecDeque,
+    sync::mpsc::{Receiver, Sender},
+};
+
+/// Provides values for the `ReadInput` instruction.
+pub trait InputSource<W = isize> {
+    /// Returns the next input value, or `None` if there is no value available (yet).
+    fn read(&mut self) -> Option<W>;
+}
+
+/// Receives values produced by the `WriteOutput` instruction.
+pub trait OutputSink<W = isize> {
+    fn write(&mut self, value: W);
+}
+
+/// An input source that can take values back, which lets `Computer::step_back` undo reads.
+pub trait RewindableInput<W = isize>: InputSource<W> {
+    /// Puts a value back, so that it is the next one read.
+    fn unread(&mut self, value: W);
+}
+
+/// An output sink that can take values back, which lets `Computer::step_back` undo writes.
+pub trait RewindableOutput<W = isize>: OutputSink<W> {
+    /// Removes the most recently written value.
+    fn unwrite(&mut self);
+}
+
+impl<W> InputSource<W> for VecDeque<W> {
+    fn read(&mut self) -> Option<W> {
+        self.pop_front()
+    }
+}
+
+impl<W> RewindableInput<W> for VecDeque<W> {
+    fn unread(&mut self, value: W) {
+        self.push_front(value);
+    }
+}
+
+impl<F, W> InputSource<W> for F
+where
+    F: FnMut() -> Option<W>,
+{
+    fn read(&mut self) -> Option<W> {
+        self()
+    }
+}
+
+/// Blocks until a value is received. Returns `None` only once all senders are dropped.
+impl<W> InputSource<W> for Receiver<W> {
+    fn read(&mut self) -> Option<W> {
+        self.recv().ok()
+    }
+}
+
+/// Adapts any iterator of values into an input source.
+pub struct IteratorInput<I>(pub I);
+
+impl<I, W> InputSource<W> for IteratorInput<I>
+where
+    I: Iterator<Item = W>,
+{
+    fn read(&mut self) -> Option<W> {
+        self.0.next()
+    }
+}
+
+impl<W> OutputSink<W> for Vec<W> {
+    fn write(&mut self, value: W) {
+        self.push(value);
+    }
+}
+
+impl<W> OutputSink<W> for VecDeque<W> {
+    fn write(&mut self, value: W) {
+        self.push_back(value);
+    }
+}
+
+impl<W> RewindableOutput<W> for Vec<W> {
+    fn unwrite(&mut self) {
+        self.pop();
+    }
+}
+
+impl<W> RewindableOutput<W> for VecDeque<W> {
+    fn unwrite(&mut self) {
+        self.pop_back();
+    }
+}
+
+impl<F, W> OutputSink<W> for F
+where
+    F: FnMut(W),
+{
+    fn write(&mut self, value: W) {
+        self(value)
+    }
+}
+
+/// Values sent after the receiver is dropped are discarded.
+impl<W> OutputSink<W> for Sender<W> {
+    fn write(&mut self, value: W) {
+        let _ = self.send(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use super::*;
+
+    #[test]
+    fn vec_deque_is_read_in_order() {
+        let mut input: VecDeque<isize> = vec![1, 2].into();
+
+        assert_eq!(input.read(), Some(1));
+        assert_eq!(input.read(), Some(2));
+        assert_eq!(input.read(), None);
+    }
+
+    #[test]
+    fn closures_can_be_used_as_io() {
+        let mut counter = 0;
+        let mut input = || {
+            counter += 1;
+            Some(counter)
+        };
+        assert_eq!(input.read(), Some(1));
+        assert_eq!(input.read(), Some(2));
+
+        let mut written = Vec::new();
+        let mut output = |v| written.push(v);
+        output.write(5);
+        assert_eq!(written, vec![5]);
+    }
+
+    #[test]
+    fn channels_can_be_used_as_io() {
+        let (mut sender, mut receiver) = channel();
+
+        sender.write(3);
+        assert_eq!(receiver.read(), Some(3));
+
+        drop(sender);
+        assert_eq!(receiver.read(), None);
+    }
+
+    #[test]
+    fn iterators_can_be_used_as_input() {
+        let mut input = IteratorInput((1..3).map(|x| x * 10));
+
+        assert_eq!(input.read(), Some(10));
+        assert_eq!(input.read(), Some(20));
+        assert_eq!(input.read(), None);
+    }
+}
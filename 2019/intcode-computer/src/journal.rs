@@ -0,0 +1,93 @@
+//! A bounded history of the changes made by executed instructions, used to step backwards.
+
+use std::collections::VecDeque;
+
+/// What an instruction changed, with the values from before it was executed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry<W> {
+    pub ip: usize,
+    pub relative_base: isize,
+    /// The written address and the value it held.
+    pub write: Option<(usize, W)>,
+    /// The value read from the input.
+    pub input: Option<W>,
+    /// Whether a value was written to the output.
+    pub output: bool,
+}
+
+/// A ring buffer of entries. Once full, the oldest entry is dropped for every new one.
+#[derive(Debug, Clone)]
+pub struct Journal<W> {
+    /// Grows as entries are pushed, so that large capacities cost nothing up front.
+    entries: VecDeque<JournalEntry<W>>,
+    capacity: usize,
+}
+
+impl<W> Journal<W> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, entry: JournalEntry<W>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+
+        self.entries.push_back(entry);
+    }
+
+    pub fn pop(&mut self) -> Option<JournalEntry<W>> {
+        self.entries.pop_back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(ip: usize) -> JournalEntry<isize> {
+        JournalEntry {
+            ip,
+            relative_base: 0,
+            write: None,
+            input: None,
+            output: false,
+        }
+    }
+
+    #[test]
+    fn accepts_huge_capacity() {
+        let mut journal = Journal::new(usize::MAX);
+        journal.push(entry(0));
+
+        assert_eq!(journal.len(), 1);
+        assert_eq!(journal.pop(), Some(entry(0)));
+    }
+
+    #[test]
+    fn drops_oldest_entries_when_full() {
+        let mut journal = Journal::new(2);
+        for ip in 0..3 {
+            journal.push(entry(ip));
+        }
+
+        assert_eq!(journal.len(), 2);
+        assert_eq!(journal.pop(), Some(entry(2)));
+        assert_eq!(journal.pop(), Some(entry(1)));
+        assert_eq!(journal.pop(), None);
+    }
+}
@@ -1,7 +1,28 @@
+pub mod analysis;
+pub mod ascii;
+pub mod assembler;
+mod cache;
 mod computer;
+pub mod debugger;
+pub mod disassembler;
+mod error;
+#[cfg(test)]
+mod fuzz;
 mod instruction;
+pub mod io;
+mod journal;
+pub mod network;
+pub mod profiler;
 pub mod program;
 mod ram;
+pub mod threaded;
+pub mod trace;
+pub mod translate;
+mod word;
 
-pub use computer::Computer;
-pub use instruction::Instruction;
+pub use computer::{Computer, RunState, Snapshot};
+pub use error::IntcodeError;
+pub use instruction::{ArgMode, HeaderError, Instruction, InstructionHeader, Opcode};
+pub use io::{InputSource, OutputSink, RewindableInput, RewindableOutput};
+pub use ram::{PageStats, PAGE_SIZE};
+pub use word::Word;
@@ -0,0 +1,391 @@
+//! Several computers connected together, exchanging their outputs.
+//!
+//! Machines are scheduled round-robin in address order. In each round every machine that has not
+//! halted runs until it needs an input value that is not available, so runs are deterministic.
+
+use std::{collections::VecDeque, error::Error, fmt::Display};
+
+use crate::{Computer, IntcodeError, RunState};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topology {
+    /// Outputs of each machine are inputs of the next one. Outputs of the last machine are
+    /// inputs of the first one.
+    Ring,
+    /// Outputs of each machine are inputs of the next one. Outputs of the last machine leave the
+    /// network as `NetworkEvent::Output`.
+    Pipeline,
+    /// Machines send packets as `(destination, x, y)` output triples. A machine reading from an
+    /// empty input queue receives -1.
+    Packets,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Packet {
+    pub destination: isize,
+    pub x: isize,
+    pub y: isize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkEvent {
+    /// A value output by the last machine of a pipeline.
+    Output(isize),
+    /// A packet sent to an address with no machine.
+    Undeliverable { from: usize, packet: Packet },
+    /// The network was idle, so the NAT sent its last packet to address 0.
+    NatDelivered(Packet),
+    /// No machine can make progress without more input, and there is no NAT packet to send.
+    Idle,
+    /// All machines halted.
+    Halted,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkError {
+    pub address: usize,
+    pub error: IntcodeError,
+}
+
+impl Error for NetworkError {}
+
+impl Display for NetworkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "machine {} failed: {}", self.address, self.error)
+    }
+}
+
+pub struct Network {
+    machines: Vec<Computer>,
+    halted: Vec<bool>,
+    topology: Topology,
+    /// Values output by each machine that do not form a full packet yet.
+    partial_packets: Vec<Vec<isize>>,
+    nat_address: Option<isize>,
+    nat_packet: Option<Packet>,
+    events: VecDeque<NetworkEvent>,
+}
+
+impl Network {
+    pub fn new(machines: Vec<Computer>, topology: Topology) -> Self {
+        let size = machines.len();
+
+        Self {
+            machines,
+            halted: vec![false; size],
+            topology,
+            partial_packets: vec![Vec::new(); size],
+            nat_address: None,
+            nat_packet: None,
+            events: VecDeque::new(),
+        }
+    }
+
+    /// A packet-switched network of `size` machines running the same program. Each machine gets
+    /// its address as the first input value.
+    pub fn packet_switched(program: &[isize], size: usize) -> Self {
+        let machines = (0..size)
+            .map(|address| Computer::new(program.to_vec(), vec![address as isize]))
+            .collect();
+
+        Self::new(machines, Topology::Packets)
+    }
+
+    /// Adds a NAT listening on `address`. It keeps the last packet sent to it and sends it to
+    /// address 0 whenever the network becomes idle.
+    pub fn with_nat(mut self, address: isize) -> Self {
+        self.nat_address = Some(address);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.machines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.machines.is_empty()
+    }
+
+    pub fn machine(&self, address: usize) -> &Computer {
+        &self.machines[address]
+    }
+
+    pub fn machine_mut(&mut self, address: usize) -> &mut Computer {
+        &mut self.machines[address]
+    }
+
+    /// The last packet received by the NAT.
+    pub fn nat_packet(&self) -> Option<Packet> {
+        self.nat_packet
+    }
+
+    /// Adds a value to the input of the machine with the given address.
+    pub fn send(&mut self, address: usize, value: isize) {
+        self.machines[address].input_mut().push_back(value);
+    }
+
+    /// Runs the machines until something that the caller may want to react to happens.
+    ///
+    /// `Idle` and `Halted` are returned again on subsequent calls if nothing changes.
+    pub fn next_event(&mut self) -> Result<NetworkEvent, NetworkError> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(event);
+            }
+
+            self.run_round()?;
+        }
+    }
+
+    /// Runs the machines until all of them halt, ignoring other events. Fails if the network
+    /// becomes idle, as it would never halt.
+    pub fn run_till_halt(&mut self) -> Result<(), NetworkError> {
+        loop {
+            match self.next_event()? {
+                NetworkEvent::Halted => return Ok(()),
+                NetworkEvent::Idle => {
+                    let address = self.halted.iter().position(|h| !h).unwrap_or(0);
+
+                    return Err(NetworkError {
+                        address,
+                        error: IntcodeError::InputExhausted {
+                            ip: self.machines[address].ip(),
+                        },
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn run_round(&mut self) -> Result<(), NetworkError> {
+        let mut sent = false;
+
+        for address in 0..self.machines.len() {
+            if self.halted[address] {
+                continue;
+            }
+
+            loop {
+                let state = self.machines[address]
+                    .run()
+                    .map_err(|error| NetworkError { address, error })?;
+
+                match state {
+                    RunState::Output(value) => {
+                        sent = true;
+                        self.route(address, value);
+                    }
+                    RunState::AwaitingInput => {
+                        if self.topology == Topology::Packets {
+                            self.send(address, -1);
+                        }
+                        break;
+                    }
+                    RunState::Halted => {
+                        self.halted[address] = true;
+                        break;
+                    }
+                }
+            }
+        }
+
+        if self.halted.iter().all(|&h| h) {
+            self.events.push_back(NetworkEvent::Halted);
+        } else if !sent {
+            self.on_idle();
+        }
+
+        Ok(())
+    }
+
+    fn route(&mut self, from: usize, value: isize) {
+        let next = from + 1;
+
+        match self.topology {
+            Topology::Ring => self.send(next % self.machines.len(), value),
+            Topology::Pipeline if next < self.machines.len() => self.send(next, value),
+            Topology::Pipeline => self.events.push_back(NetworkEvent::Output(value)),
+            Topology::Packets => {
+                let partial = &mut self.partial_packets[from];
+                partial.push(value);
+                if partial.len() < 3 {
+                    return;
+                }
+
+                let packet = Packet {
+                    destination: partial[0],
+                    x: partial[1],
+                    y: partial[2],
+                };
+                partial.clear();
+                self.deliver(from, packet);
+            }
+        }
+    }
+
+    fn deliver(&mut self, from: usize, packet: Packet) {
+        if Some(packet.destination) == self.nat_address {
+            self.nat_packet = Some(packet);
+        } else if packet.destination >= 0 && (packet.destination as usize) < self.machines.len() {
+            let address = packet.destination as usize;
+            self.send(address, packet.x);
+            self.send(address, packet.y);
+        } else {
+            self.events
+                .push_back(NetworkEvent::Undeliverable { from, packet });
+        }
+    }
+
+    fn on_idle(&mut self) {
+        match self.nat_packet {
+            Some(packet) if !self.machines.is_empty() => {
+                self.send(0, packet.x);
+                self.send(0, packet.y);
+                self.events.push_back(NetworkEvent::NatDelivered(packet));
+            }
+            _ => self.events.push_back(NetworkEvent::Idle),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+
+    fn machine(source: &str, input: Vec<isize>) -> Computer {
+        Computer::new(assemble(source).unwrap(), input)
+    }
+
+    const INCREMENT: &str = "
+        loop:   in [x]
+                add [x], #1, [x]
+                out [x]
+                jt #1, loop
+        x:      .data 0
+    ";
+
+    // Adds the phase setting (the first input) to 3 input values
+    const ADD_PHASE: &str = "
+                in [phase]
+        loop:   in [x]
+                add [x], [phase], [x]
+                out [x]
+                add [n], #-1, [n]
+                jt [n], loop
+                hlt
+        phase:  .data 0
+        x:      .data 0
+        n:      .data 3
+    ";
+
+    // Machine 0 sends (1, 10, 20). Every machine forwards received packets to the next address,
+    // increasing x by 1.
+    const FORWARD: &str = "
+                in [addr]
+                jt [addr], receive
+                out #1
+                out #10
+                out #20
+        receive:
+                in [x]
+                eq [x], #-1, [tmp]
+                jt [tmp], receive
+                in [y]
+                add [addr], #1, [dest]
+                add [x], #1, [x]
+                out [dest]
+                out [x]
+                out [y]
+                jt #1, receive
+        addr:   .data 0
+        x:      .data 0
+        y:      .data 0
+        dest:   .data 0
+        tmp:    .data 0
+    ";
+
+    #[test]
+    fn pipeline_passes_values_through_machines() {
+        let machines = (0..3).map(|_| machine(INCREMENT, vec![])).collect();
+        let mut network = Network::new(machines, Topology::Pipeline);
+        network.send(0, 10);
+        network.send(0, 20);
+
+        assert_eq!(network.next_event(), Ok(NetworkEvent::Output(13)));
+        assert_eq!(network.next_event(), Ok(NetworkEvent::Output(23)));
+        assert_eq!(network.next_event(), Ok(NetworkEvent::Idle));
+    }
+
+    #[test]
+    fn ring_feeds_last_output_back() {
+        let machines = (1..=3)
+            .map(|phase| machine(ADD_PHASE, vec![phase]))
+            .collect();
+        let mut network = Network::new(machines, Topology::Ring);
+        network.send(0, 0);
+
+        assert_eq!(network.run_till_halt(), Ok(()));
+        assert_eq!(network.machine(2).output(), &vec![6, 12, 18]);
+        assert_eq!(network.next_event(), Ok(NetworkEvent::Halted));
+    }
+
+    #[test]
+    fn idle_ring_fails_to_halt() {
+        let machines = (1..=3)
+            .map(|phase| machine(ADD_PHASE, vec![phase]))
+            .collect();
+        let mut network = Network::new(machines, Topology::Ring);
+
+        assert_eq!(
+            network.run_till_halt(),
+            Err(NetworkError {
+                address: 0,
+                error: IntcodeError::InputExhausted { ip: 2 }
+            })
+        );
+    }
+
+    #[test]
+    fn routes_packets() {
+        let program = assemble(FORWARD).unwrap();
+        let mut network = Network::packet_switched(&program, 2);
+
+        assert_eq!(
+            network.next_event(),
+            Ok(NetworkEvent::Undeliverable {
+                from: 1,
+                packet: Packet {
+                    destination: 2,
+                    x: 11,
+                    y: 20
+                }
+            })
+        );
+        assert_eq!(network.next_event(), Ok(NetworkEvent::Idle));
+    }
+
+    #[test]
+    fn nat_wakes_up_idle_network() {
+        let program = assemble(FORWARD).unwrap();
+        let mut network = Network::packet_switched(&program, 2).with_nat(2);
+
+        let delivered: Vec<isize> = (0..3)
+            .map(|_| match network.next_event() {
+                Ok(NetworkEvent::NatDelivered(packet)) => packet.x,
+                event => panic!("unexpected event {:?}", event),
+            })
+            .collect();
+
+        assert_eq!(delivered, vec![11, 13, 15]);
+        assert_eq!(
+            network.nat_packet(),
+            Some(Packet {
+                destination: 2,
+                x: 15,
+                y: 20
+            })
+        );
+    }
+}
@@ -0,0 +1,203 @@
+//! Counting of executed instructions, to find the hot loops of a program.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    disassembler::disassemble_instruction,
+    instruction::Opcode,
+    io::{InputSource, OutputSink},
+    trace::{TraceStep, Tracer},
+    Computer,
+};
+
+/// Collects execution counts of a `Computer` when used as its tracer.
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    steps: u64,
+    address_counts: HashMap<usize, u64>,
+    opcode_counts: HashMap<Opcode, u64>,
+    steps_since_output: u64,
+    output_intervals: Vec<u64>,
+}
+
+impl Profiler {
+    /// Adds a new profiler to the tracers of the computer, keeping any tracer already set. The
+    /// returned handle can be used to read the counts at any time.
+    pub fn attach<I, O>(computer: &mut Computer<I, O>) -> Arc<Mutex<Profiler>>
+    where
+        I: InputSource,
+        O: OutputSink,
+    {
+        let profiler = Arc::new(Mutex::new(Profiler::default()));
+        computer.add_tracer(Arc::clone(&profiler));
+
+        profiler
+    }
+
+    /// Number of executed instructions.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Addresses of executed instructions with their execution counts, most executed first.
+    pub fn hotspots(&self) -> Vec<(usize, u64)> {
+        let mut hotspots: Vec<(usize, u64)> =
+            self.address_counts.iter().map(|(&a, &c)| (a, c)).collect();
+        hotspots.sort_by_key(|&(addr, count)| (std::cmp::Reverse(count), addr));
+
+        hotspots
+    }
+
+    /// Execution counts of opcodes, most executed first.
+    pub fn opcode_counts(&self) -> Vec<(Opcode, u64)> {
+        let mut counts: Vec<(Opcode, u64)> = Opcode::ALL
+            .iter()
+            .filter_map(|o| self.opcode_counts.get(o).map(|&c| (*o, c)))
+            .collect();
+        counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+
+        counts
+    }
+
+    /// Numbers of instructions executed before each output, counting from the previous output,
+    /// including the output instruction itself.
+    pub fn output_intervals(&self) -> &[u64] {
+        &self.output_intervals
+    }
+
+    /// A summary of the counts with the `top` most executed addresses. If `program` is given,
+    /// the instructions at these addresses are disassembled.
+    pub fn report(&self, program: Option<&[isize]>, top: usize) -> String {
+        let percent = |count: u64| 100.0 * count as f64 / self.steps.max(1) as f64;
+        let mut report = format!(
+            "Executed {} instructions, {} outputs\n",
+            self.steps,
+            self.output_intervals.len()
+        );
+
+        if let (Some(min), Some(max)) = (
+            self.output_intervals.iter().min(),
+            self.output_intervals.iter().max(),
+        ) {
+            let sum: u64 = self.output_intervals.iter().sum();
+            report.push_str(&format!(
+                "Steps between outputs: min {}, avg {:.1}, max {}\n",
+                min,
+                sum as f64 / self.output_intervals.len() as f64,
+                max
+            ));
+        }
+
+        report.push_str("\nOpcodes:\n");
+        for (opcode, count) in self.opcode_counts() {
+            report.push_str(&format!(
+                "  {:<4} {:>12} {:>6.2}%\n",
+                opcode.mnemonic(),
+                count,
+                percent(count)
+            ));
+        }
+
+        report.push_str("\nHotspots:\n");
+        for (addr, count) in self.hotspots().into_iter().take(top) {
+            let line = format!("  {:>6}: {:>12} {:>6.2}%", addr, count, percent(count));
+            let instruction = program
+                .and_then(|p| p.get(addr..))
+                .and_then(disassemble_instruction);
+
+            match instruction {
+                Some(instruction) => report.push_str(&format!("{}   {}\n", line, instruction)),
+                None => report.push_str(&format!("{}\n", line)),
+            }
+        }
+
+        report
+    }
+}
+
+impl Tracer for Profiler {
+    fn record(&mut self, step: &TraceStep) {
+        let opcode = step.instruction.opcode();
+
+        self.steps += 1;
+        self.steps_since_output += 1;
+        *self.address_counts.entry(step.ip).or_insert(0) += 1;
+        *self.opcode_counts.entry(opcode).or_insert(0) += 1;
+
+        if opcode == Opcode::WriteOutput {
+            self.output_intervals.push(self.steps_since_output);
+            self.steps_since_output = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+
+    const COUNTDOWN: &str = "
+                in [n]
+        loop:   out [n]
+                add [n], #-1, [n]
+                jt [n], loop
+                hlt
+        n:      .data 0
+    ";
+
+    #[test]
+    fn counts_executions() {
+        let program = assemble(COUNTDOWN).unwrap();
+        let mut computer = Computer::new(program, vec![3]);
+        let profiler = Profiler::attach(&mut computer);
+        computer.run_till_halt().unwrap();
+
+        let profiler = profiler.lock().unwrap();
+        assert_eq!(profiler.steps(), 10);
+        assert_eq!(profiler.hotspots(), vec![(2, 3), (4, 3), (8, 3), (0, 1)]);
+        assert_eq!(profiler.opcode_counts()[3], (Opcode::ReadInput, 1));
+        assert_eq!(profiler.output_intervals(), &[2, 3, 3]);
+    }
+
+    #[test]
+    fn keeps_existing_tracer() {
+        let program = assemble(COUNTDOWN).unwrap();
+        let mut computer = Computer::new(program, vec![3]);
+        let steps = Arc::new(Mutex::new(Vec::new()));
+        computer.set_tracer(Arc::clone(&steps));
+        let profiler = Profiler::attach(&mut computer);
+        computer.run_till_halt().unwrap();
+
+        assert_eq!(steps.lock().unwrap().len(), 10);
+        assert_eq!(profiler.lock().unwrap().steps(), 10);
+    }
+
+    #[test]
+    fn reports_hotspots() {
+        let program = assemble(COUNTDOWN).unwrap();
+        let mut computer = Computer::new(program.clone(), vec![2]);
+        let profiler = Profiler::attach(&mut computer);
+        computer.run_till_halt().unwrap();
+
+        let report = profiler.lock().unwrap().report(Some(&program), 2);
+        assert_eq!(
+            report,
+            "Executed 7 instructions, 2 outputs
+Steps between outputs: min 2, avg 2.5, max 3
+
+Opcodes:
+  add             2  28.57%
+  out             2  28.57%
+  jt              2  28.57%
+  in              1  14.29%
+
+Hotspots:
+       2:            2  28.57%   out [12]
+       4:            2  28.57%   add [12], #-1, [12]
+"
+        );
+    }
+}
@@ -1,28 +1,299 @@
-pub struct RAM {
-    memory: Vec<isize>,
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryInto,
+};
+
+use crate::word::Word;
+
+const PAGE_BITS: usize = 10;
+/// Number of memory cells in a page.
+pub const PAGE_SIZE: usize = 1 << PAGE_BITS;
+/// Pages with lower numbers are looked up by index, the rest in a hash map.
+const DIRECT_PAGES: usize = 1 << 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryError {
+    LimitExceeded { address: usize },
+    ProtectedWrite { address: usize },
+}
+
+/// Numbers of accesses to a page of memory since the program was loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageStats {
+    /// Address of the first cell of the page.
+    pub start: usize,
+    pub reads: u64,
+    pub writes: u64,
+}
+
+#[derive(Clone)]
+struct Page<W> {
+    cells: Box<[W; PAGE_SIZE]>,
+    reads: u64,
+    writes: u64,
+}
+
+impl<W: Word> Page<W> {
+    fn new() -> Self {
+        let cells = vec![W::zero(); PAGE_SIZE].into_boxed_slice();
+
+        Self {
+            cells: cells.try_into().ok().unwrap(),
+            reads: 0,
+            writes: 0,
+        }
+    }
+}
+
+/// Memory allocated one page at a time, only when a page is written to. Reading a cell that was
+/// never written gives 0.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone)]
+pub struct RAM<W = isize> {
+    direct: Vec<Option<Page<W>>>,
+    far: HashMap<usize, Page<W>>,
+    /// Numbers of reads of pages that were never written to, by page number.
+    unmapped_reads: HashMap<usize, u64>,
+    /// Accessing cells at or above this address fails.
+    limit: Option<usize>,
+    image_len: usize,
+    protect_image: bool,
 }
 
-impl RAM {
-    pub fn new(memory: Vec<isize>) -> Self {
-        Self { memory }
+impl<W: Word> RAM<W> {
+    pub fn new(program: Vec<W>) -> Self {
+        let mut ram = Self {
+            direct: Vec::new(),
+            far: HashMap::new(),
+            unmapped_reads: HashMap::new(),
+            limit: None,
+            image_len: program.len(),
+            protect_image: false,
+        };
+
+        for (page_number, chunk) in program.chunks(PAGE_SIZE).enumerate() {
+            ram.page_or_insert(page_number).cells[..chunk.len()].clone_from_slice(chunk);
+        }
+
+        ram
+    }
+
+    /// Rebuilds the memory from the result of `pages`.
+    pub fn from_pages(pages: &BTreeMap<usize, Vec<W>>) -> Self {
+        let mut ram = Self::new(Vec::new());
+        ram.load_pages(pages);
+
+        ram
     }
 
-    pub fn get(&mut self, addr: usize) -> isize {
-        match self.memory.get(addr) {
-            Some(x) => *x,
+    /// Replaces the contents of the memory with the result of `pages` and resets the access
+    /// counts. The limit and the protected program image are kept.
+    pub fn load_pages(&mut self, pages: &BTreeMap<usize, Vec<W>>) {
+        self.direct.clear();
+        self.far.clear();
+        self.unmapped_reads.clear();
+
+        for (&start, cells) in pages {
+            let page = self.page_or_insert(start >> PAGE_BITS);
+            let len = cells.len().min(PAGE_SIZE);
+            page.cells[..len].clone_from_slice(&cells[..len]);
+        }
+    }
+
+    /// The contents of all allocated pages, by the address of their first cell.
+    pub fn pages(&self) -> BTreeMap<usize, Vec<W>> {
+        self.allocated_pages()
+            .map(|(number, page)| (number << PAGE_BITS, page.cells.to_vec()))
+            .collect()
+    }
+
+    pub fn get(&mut self, addr: usize) -> Result<W, MemoryError> {
+        self.check_limit(addr)?;
+        let number = addr >> PAGE_BITS;
+
+        match self.page_mut(number) {
+            Some(page) => {
+                page.reads += 1;
+
+                Ok(page.cells[addr % PAGE_SIZE].clone())
+            }
             None => {
-                self.memory.resize(addr + 1, 0);
+                *self.unmapped_reads.entry(number).or_insert(0) += 1;
 
-                0
+                Ok(W::zero())
             }
         }
     }
 
-    pub fn set(&mut self, addr: usize, val: isize) {
-        if self.memory.len() <= addr {
-            self.memory.resize(addr + 1, 0);
+    /// Reads a cell without counting the access or checking the limit.
+    pub fn peek(&self, addr: usize) -> W {
+        self.page(addr >> PAGE_BITS)
+            .map_or_else(W::zero, |page| page.cells[addr % PAGE_SIZE].clone())
+    }
+
+    pub fn set(&mut self, addr: usize, val: W) -> Result<(), MemoryError> {
+        self.check_limit(addr)?;
+        if self.protect_image && addr < self.image_len {
+            return Err(MemoryError::ProtectedWrite { address: addr });
         }
 
-        self.memory[addr] = val;
+        let page = self.page_or_insert(addr >> PAGE_BITS);
+        page.writes += 1;
+        page.cells[addr % PAGE_SIZE] = val;
+
+        Ok(())
+    }
+
+    /// Writes a cell without counting the access or checking the limit and protection.
+    pub fn poke(&mut self, addr: usize, val: W) {
+        let page = self.page_or_insert(addr >> PAGE_BITS);
+        page.cells[addr % PAGE_SIZE] = val;
+    }
+
+    pub fn set_limit(&mut self, limit: Option<usize>) {
+        self.limit = limit;
+    }
+
+    /// Makes writes to the cells of the loaded program fail.
+    pub fn set_image_protection(&mut self, enabled: bool) {
+        self.protect_image = enabled;
+    }
+
+    /// Access counts of every page that was accessed, ordered by address.
+    pub fn stats(&self) -> Vec<PageStats> {
+        let mut stats: Vec<PageStats> = self
+            .allocated_pages()
+            .map(|(number, page)| PageStats {
+                start: number << PAGE_BITS,
+                reads: page.reads + self.unmapped_reads.get(&number).cloned().unwrap_or(0),
+                writes: page.writes,
+            })
+            .chain(
+                self.unmapped_reads
+                    .iter()
+                    .filter(|(&number, _)| self.page(number).is_none())
+                    .map(|(&number, &reads)| PageStats {
+                        start: number << PAGE_BITS,
+                        reads,
+                        writes: 0,
+                    }),
+            )
+            .filter(|s| s.reads > 0 || s.writes > 0)
+            .collect();
+        stats.sort_by_key(|s| s.start);
+
+        stats
+    }
+
+    fn check_limit(&self, addr: usize) -> Result<(), MemoryError> {
+        match self.limit {
+            Some(limit) if addr >= limit => Err(MemoryError::LimitExceeded { address: addr }),
+            _ => Ok(()),
+        }
+    }
+
+    fn allocated_pages(&self) -> impl Iterator<Item = (usize, &Page<W>)> {
+        self.direct
+            .iter()
+            .enumerate()
+            .filter_map(|(number, page)| page.as_ref().map(|p| (number, p)))
+            .chain(self.far.iter().map(|(&number, page)| (number, page)))
+    }
+
+    fn page(&self, number: usize) -> Option<&Page<W>> {
+        if number < DIRECT_PAGES {
+            self.direct.get(number).and_then(Option::as_ref)
+        } else {
+            self.far.get(&number)
+        }
+    }
+
+    fn page_mut(&mut self, number: usize) -> Option<&mut Page<W>> {
+        if number < DIRECT_PAGES {
+            self.direct.get_mut(number).and_then(Option::as_mut)
+        } else {
+            self.far.get_mut(&number)
+        }
+    }
+
+    fn page_or_insert(&mut self, number: usize) -> &mut Page<W> {
+        if number < DIRECT_PAGES {
+            if self.direct.len() <= number {
+                self.direct.resize_with(number + 1, || None);
+            }
+
+            self.direct[number].get_or_insert_with(Page::new)
+        } else {
+            self.far.entry(number).or_insert_with(Page::new)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn far_addresses_do_not_allocate_everything_below() {
+        let mut ram: RAM = RAM::new(vec![1, 2, 3]);
+
+        assert_eq!(ram.get(1_000_000_000_000), Ok(0));
+        ram.set(1_000_000_000_001, 7).unwrap();
+        assert_eq!(ram.get(1_000_000_000_001), Ok(7));
+        assert_eq!(ram.get(2), Ok(3));
+        assert_eq!(ram.pages().len(), 2);
+    }
+
+    #[test]
+    fn enforces_limit_and_protection() {
+        let mut ram: RAM = RAM::new(vec![1, 2, 3]);
+        ram.set_limit(Some(100));
+        ram.set_image_protection(true);
+
+        assert_eq!(
+            ram.get(100),
+            Err(MemoryError::LimitExceeded { address: 100 })
+        );
+        assert_eq!(
+            ram.set(2, 0),
+            Err(MemoryError::ProtectedWrite { address: 2 })
+        );
+        assert_eq!(ram.set(3, 0), Ok(()));
+    }
+
+    #[test]
+    fn counts_accesses_per_page() {
+        let mut ram: RAM = RAM::new(vec![1, 2, 3]);
+        ram.get(0).unwrap();
+        ram.set(1, 5).unwrap();
+        ram.get(PAGE_SIZE * 5000).unwrap();
+        ram.get(PAGE_SIZE * 5000 + 1).unwrap();
+
+        assert_eq!(
+            ram.stats(),
+            vec![
+                PageStats {
+                    start: 0,
+                    reads: 1,
+                    writes: 1
+                },
+                PageStats {
+                    start: PAGE_SIZE * 5000,
+                    reads: 2,
+                    writes: 0
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn pages_round_trip() {
+        let mut ram: RAM = RAM::new(vec![1, 2, 3]);
+        ram.set(PAGE_SIZE * 9000 + 3, 4).unwrap();
+        let restored = RAM::from_pages(&ram.pages());
+
+        assert_eq!(restored.peek(2), 3);
+        assert_eq!(restored.peek(PAGE_SIZE * 9000 + 3), 4);
+        assert_eq!(restored.pages(), ram.pages());
     }
 }
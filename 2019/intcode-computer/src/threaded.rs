@@ -0,0 +1,386 @@
+//! Computers running on separate threads, connected by channels.
+//!
+//! The controlling thread is a participant too: it can send values to machines and receive the
+//! outputs of machines that are not connected to other machines. A deadlock is detected when all
+//! participants wait for input and no values are in flight, which makes the waiting machines
+//! stop.
+
+use std::{
+    error::Error,
+    fmt::Display,
+    sync::{
+        mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{Computer, InputSource, IntcodeError, OutputSink};
+
+/// How often waiting participants check whether a deadlock was detected.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ThreadedError {
+    Intcode {
+        address: usize,
+        error: IntcodeError,
+    },
+    /// All machines were waiting for input that could never arrive.
+    Deadlock {
+        waiting: Vec<usize>,
+    },
+    Panicked {
+        address: usize,
+    },
+}
+
+impl Error for ThreadedError {}
+
+impl Display for ThreadedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ThreadedError::Intcode { address, error } => {
+                write!(f, "machine {} failed: {}", address, error)
+            }
+            ThreadedError::Deadlock { waiting } => {
+                write!(f, "deadlock, machines {:?} wait for input", waiting)
+            }
+            ThreadedError::Panicked { address } => write!(f, "machine {} panicked", address),
+        }
+    }
+}
+
+struct MachineSpec {
+    program: Vec<isize>,
+    input: Vec<isize>,
+    output_to: Option<usize>,
+}
+
+/// Describes machines and the connections between them before starting them.
+#[derive(Default)]
+pub struct ThreadedRunner {
+    machines: Vec<MachineSpec>,
+}
+
+impl ThreadedRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a machine with the given initial input. Returns its address.
+    pub fn add_machine(&mut self, program: Vec<isize>, input: Vec<isize>) -> usize {
+        self.machines.push(MachineSpec {
+            program,
+            input,
+            output_to: None,
+        });
+
+        self.machines.len() - 1
+    }
+
+    /// Sends the outputs of `from` to the input of `to`. Outputs of machines that are not
+    /// connected go to `RunningMachines::recv_output`.
+    pub fn connect(&mut self, from: usize, to: usize) {
+        self.machines[from].output_to = Some(to);
+    }
+
+    pub fn start(self) -> RunningMachines {
+        let controller = self.machines.len();
+        let monitor = Arc::new(Monitor::new(self.machines.len() + 1));
+
+        let (senders, receivers): (Vec<_>, Vec<_>) =
+            (0..=controller).map(|_| channel::<isize>()).unzip();
+
+        let mut receivers = receivers.into_iter();
+        let mut handles = Vec::new();
+        for (address, spec) in self.machines.into_iter().enumerate() {
+            let input = ChannelInput {
+                address,
+                receiver: receivers.next().unwrap(),
+                monitor: Arc::clone(&monitor),
+            };
+            let destination = spec.output_to.unwrap_or(controller);
+            let output = ChannelOutput {
+                destination,
+                sender: senders[destination].clone(),
+                monitor: Arc::clone(&monitor),
+                written: Vec::new(),
+            };
+
+            for &value in &spec.input {
+                monitor.send(address, value, &senders[address]);
+            }
+
+            handles.push(thread::spawn(move || {
+                let mut computer = Computer::with_io(spec.program, input, output);
+                let result = computer.run_till_halt();
+                computer.input().finish();
+
+                result.map(|_| computer.output().written.clone())
+            }));
+        }
+
+        RunningMachines {
+            controller: ChannelInput {
+                address: controller,
+                receiver: receivers.next().unwrap(),
+                monitor: Arc::clone(&monitor),
+            },
+            senders,
+            handles,
+            monitor,
+        }
+    }
+}
+
+/// Machines started by `ThreadedRunner::start`.
+pub struct RunningMachines {
+    controller: ChannelInput,
+    senders: Vec<Sender<isize>>,
+    handles: Vec<JoinHandle<Result<Vec<isize>, IntcodeError>>>,
+    monitor: Arc<Monitor>,
+}
+
+impl RunningMachines {
+    /// Adds a value to the input of the machine with the given address.
+    pub fn send(&self, address: usize, value: isize) {
+        self.monitor.send(address, value, &self.senders[address]);
+    }
+
+    /// Waits for an output of a machine that is not connected to another machine. Returns `None`
+    /// once no such output can arrive anymore.
+    pub fn recv_output(&mut self) -> Option<isize> {
+        self.controller.read()
+    }
+
+    /// Waits for all machines to stop. Returns the outputs of every machine.
+    pub fn join(self) -> Result<Vec<Vec<isize>>, ThreadedError> {
+        self.controller.finish();
+
+        let results: Vec<_> = self.handles.into_iter().map(JoinHandle::join).collect();
+        let deadlock = self.monitor.state.lock().unwrap().deadlock.clone();
+
+        let mut outputs = Vec::new();
+        for (address, result) in results.into_iter().enumerate() {
+            match result {
+                Ok(Ok(written)) => outputs.push(written),
+                Ok(Err(IntcodeError::InputExhausted { .. })) if deadlock.is_some() => {}
+                Ok(Err(error)) => return Err(ThreadedError::Intcode { address, error }),
+                Err(_) => return Err(ThreadedError::Panicked { address }),
+            }
+        }
+
+        match deadlock {
+            Some(waiting) => Err(ThreadedError::Deadlock { waiting }),
+            None => Ok(outputs),
+        }
+    }
+}
+
+/// Participants are the machines, followed by the controlling thread.
+struct State {
+    running: Vec<bool>,
+    waiting: Vec<bool>,
+    /// Number of values sent, but not received yet.
+    in_flight: usize,
+    /// Machines that were waiting when a deadlock was detected.
+    deadlock: Option<Vec<usize>>,
+}
+
+struct Monitor {
+    state: Mutex<State>,
+}
+
+impl Monitor {
+    fn new(participants: usize) -> Self {
+        Self {
+            state: Mutex::new(State {
+                running: vec![true; participants],
+                waiting: vec![false; participants],
+                in_flight: 0,
+                deadlock: None,
+            }),
+        }
+    }
+
+    /// Values sent to participants that stopped are discarded.
+    fn send(&self, to: usize, value: isize, sender: &Sender<isize>) {
+        let mut state = self.state.lock().unwrap();
+        if state.running[to] && sender.send(value).is_ok() {
+            state.in_flight += 1;
+        }
+    }
+
+    fn receive(&self, address: usize, receiver: &Receiver<isize>) -> Option<isize> {
+        if let Ok(value) = receiver.try_recv() {
+            self.state.lock().unwrap().in_flight -= 1;
+            return Some(value);
+        }
+
+        {
+            let mut state = self.state.lock().unwrap();
+            state.waiting[address] = true;
+            Self::check_deadlock(&mut state);
+        }
+
+        loop {
+            let received = receiver.recv_timeout(POLL_INTERVAL);
+            let mut state = self.state.lock().unwrap();
+
+            match received {
+                Ok(value) => {
+                    state.waiting[address] = false;
+                    state.in_flight -= 1;
+                    return Some(value);
+                }
+                Err(RecvTimeoutError::Timeout) if state.deadlock.is_none() => {}
+                Err(_) => {
+                    state.waiting[address] = false;
+                    return None;
+                }
+            }
+        }
+    }
+
+    fn finish(&self, address: usize, receiver: &Receiver<isize>) {
+        let mut state = self.state.lock().unwrap();
+        state.running[address] = false;
+        state.waiting[address] = false;
+        while receiver.try_recv().is_ok() {
+            state.in_flight -= 1;
+        }
+
+        Self::check_deadlock(&mut state);
+    }
+
+    fn check_deadlock(state: &mut State) {
+        let mut running = state.running.iter().zip(&state.waiting).filter(|(&r, _)| r);
+        let all_waiting = running.clone().count() > 0 && running.all(|(_, &w)| w);
+
+        if all_waiting && state.in_flight == 0 && state.deadlock.is_none() {
+            // The controller is the last participant and is not a machine
+            let machines = state.running.len() - 1;
+            state.deadlock = Some(
+                (0..machines)
+                    .filter(|&address| state.running[address] && state.waiting[address])
+                    .collect(),
+            );
+        }
+    }
+}
+
+struct ChannelInput {
+    address: usize,
+    receiver: Receiver<isize>,
+    monitor: Arc<Monitor>,
+}
+
+impl ChannelInput {
+    fn finish(&self) {
+        self.monitor.finish(self.address, &self.receiver);
+    }
+}
+
+/// Blocks until a value arrives. Returns `None` if a deadlock is detected.
+impl InputSource for ChannelInput {
+    fn read(&mut self) -> Option<isize> {
+        self.monitor.receive(self.address, &self.receiver)
+    }
+}
+
+struct ChannelOutput {
+    destination: usize,
+    sender: Sender<isize>,
+    monitor: Arc<Monitor>,
+    written: Vec<isize>,
+}
+
+impl OutputSink for ChannelOutput {
+    fn write(&mut self, value: isize) {
+        self.written.push(value);
+        self.monitor.send(self.destination, value, &self.sender);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+
+    // Example from 2019/07 part 2, with a maximum thruster signal of 139629729
+    const FEEDBACK_AMPLIFIER: [isize; 29] = [
+        3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1, 28,
+        1005, 28, 6, 99, 0, 0, 5,
+    ];
+
+    #[test]
+    fn computer_is_send() {
+        fn assert_send<T: Send>() {}
+
+        assert_send::<Computer>();
+    }
+
+    #[test]
+    fn runs_feedback_loop() {
+        let mut runner = ThreadedRunner::new();
+        for phase in &[9, 8, 7, 6, 5] {
+            runner.add_machine(FEEDBACK_AMPLIFIER.to_vec(), vec![*phase]);
+        }
+        for address in 0..5 {
+            runner.connect(address, (address + 1) % 5);
+        }
+
+        let machines = runner.start();
+        machines.send(0, 0);
+        let outputs = machines.join().unwrap();
+
+        assert_eq!(outputs[4].last(), Some(&139629729));
+    }
+
+    #[test]
+    fn receives_pipeline_outputs() {
+        let double = assemble(
+            "
+            loop:   in [x]
+                    mul [x], #2, [x]
+                    out [x]
+                    jt #1, loop
+            x:      .data 0
+            ",
+        )
+        .unwrap();
+        let mut runner = ThreadedRunner::new();
+        let first = runner.add_machine(double.clone(), vec![1, 2, 3]);
+        let second = runner.add_machine(double, vec![]);
+        runner.connect(first, second);
+
+        let mut machines = runner.start();
+        let received: Vec<isize> = (0..3).filter_map(|_| machines.recv_output()).collect();
+        assert_eq!(received, vec![4, 8, 12]);
+
+        assert_eq!(machines.recv_output(), None);
+        assert_eq!(
+            machines.join(),
+            Err(ThreadedError::Deadlock {
+                waiting: vec![first, second]
+            })
+        );
+    }
+
+    #[test]
+    fn reports_failed_machines() {
+        let mut runner = ThreadedRunner::new();
+        runner.add_machine(vec![3, 5, 99], vec![1]);
+        runner.add_machine(vec![42], vec![]);
+
+        assert_eq!(
+            runner.start().join(),
+            Err(ThreadedError::Intcode {
+                address: 1,
+                error: IntcodeError::InvalidOpcode { ip: 0, value: 42 }
+            })
+        );
+    }
+}
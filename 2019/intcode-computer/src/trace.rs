@@ -0,0 +1,658 @@
+//! Recording of executed instructions and replaying them to reconstruct machine state.
+//!
+//! Traces can be streamed to JSON lines (one object per step, easy to diff with standard tools)
+//! or to a compact binary format using variable-length integers.
+
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::Display,
+    io::{self, BufRead, Read, Write},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    instruction::{Instruction, Opcode},
+    ram::{PAGE_SIZE, RAM},
+};
+
+/// A single executed instruction together with its effects on the machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceStep {
+    pub ip: usize,
+    pub instruction: Instruction,
+    /// The memory cell written by the instruction and its new value.
+    pub write: Option<(usize, isize)>,
+    /// The new relative base, if the instruction changed it.
+    pub relative_base: Option<isize>,
+}
+
+impl TraceStep {
+    pub fn next_ip(&self) -> usize {
+        self.instruction.next_ip(self.ip)
+    }
+}
+
+pub trait Tracer {
+    fn record(&mut self, step: &TraceStep);
+}
+
+impl Tracer for Vec<TraceStep> {
+    fn record(&mut self, step: &TraceStep) {
+        self.push(step.clone());
+    }
+}
+
+/// Several tracers used as one, each recording every step in order.
+pub(crate) struct Tracers(pub(crate) Vec<Box<dyn Tracer + Send>>);
+
+impl Tracer for Tracers {
+    fn record(&mut self, step: &TraceStep) {
+        for tracer in &mut self.0 {
+            tracer.record(step);
+        }
+    }
+}
+
+/// Allows keeping a handle to a tracer that is owned by a `Computer`.
+impl<T: Tracer> Tracer for Arc<Mutex<T>> {
+    fn record(&mut self, step: &TraceStep) {
+        self.lock().unwrap().record(step);
+    }
+}
+
+#[derive(Debug)]
+pub enum TraceError {
+    Io(io::Error),
+    Malformed { step: usize, message: String },
+}
+
+impl Error for TraceError {}
+
+impl Display for TraceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TraceError::Io(e) => write!(f, "cannot read trace: {}", e),
+            TraceError::Malformed { step, message } => {
+                write!(f, "malformed trace at step {}: {}", step, message)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for TraceError {
+    fn from(e: io::Error) -> Self {
+        TraceError::Io(e)
+    }
+}
+
+/// Writes each step as a JSON object on a separate line:
+///
+/// ```text
+/// {"ip":0,"op":"add","args":[1,2,12],"write":[12,3],"rb":null}
+/// ```
+///
+/// Write errors are kept until `flush` is called, which reports the first one.
+pub struct JsonLinesTracer<W: Write> {
+    writer: W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> JsonLinesTracer<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            error: None,
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.writer.flush(),
+        }
+    }
+}
+
+impl<W: Write> Tracer for JsonLinesTracer<W> {
+    fn record(&mut self, step: &TraceStep) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let args: Vec<String> = step
+            .instruction
+            .args()
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        let write = match step.write {
+            Some((addr, value)) => format!("[{},{}]", addr, value),
+            None => "null".to_string(),
+        };
+        let relative_base = match step.relative_base {
+            Some(rb) => rb.to_string(),
+            None => "null".to_string(),
+        };
+
+        if let Err(e) = writeln!(
+            self.writer,
+            r#"{{"ip":{},"op":"{}","args":[{}],"write":{},"rb":{}}}"#,
+            step.ip,
+            step.instruction.opcode().mnemonic(),
+            args.join(","),
+            write,
+            relative_base
+        ) {
+            self.error = Some(e);
+        }
+    }
+}
+
+pub fn read_json_lines(reader: impl BufRead) -> Result<Vec<TraceStep>, TraceError> {
+    let mut steps = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let step = parse_json_step(&line).map_err(|message| TraceError::Malformed {
+            step: steps.len(),
+            message,
+        })?;
+        steps.push(step);
+    }
+
+    Ok(steps)
+}
+
+#[derive(Debug, PartialEq)]
+enum JsonValue {
+    Null,
+    Number(isize),
+    String(String),
+    Array(Vec<isize>),
+}
+
+/// Parses the objects written by `JsonLinesTracer`. Only supports what the tracer writes.
+fn parse_json_step(line: &str) -> Result<TraceStep, String> {
+    let body = line
+        .trim()
+        .strip_prefix('{')
+        .and_then(|l| l.strip_suffix('}'))
+        .ok_or("expected an object")?;
+
+    let mut ip = None;
+    let mut opcode = None;
+    let mut args = None;
+    let mut write = None;
+    let mut relative_base = None;
+
+    let mut rest = body.trim();
+    while !rest.is_empty() {
+        let (key, after_key) = parse_json_string(rest)?;
+        let after_colon = after_key
+            .trim_start()
+            .strip_prefix(':')
+            .ok_or("expected :")?;
+        let (value, after_value) = parse_json_value(after_colon.trim_start())?;
+
+        match (key.as_str(), value) {
+            ("ip", JsonValue::Number(n)) if n >= 0 => ip = Some(n as usize),
+            ("op", JsonValue::String(s)) => {
+                opcode = Some(Opcode::from_mnemonic(&s).ok_or("unknown op")?);
+            }
+            ("args", JsonValue::Array(a)) => args = Some(a),
+            ("write", JsonValue::Array(a)) if a.len() == 2 && a[0] >= 0 => {
+                write = Some(Some((a[0] as usize, a[1])))
+            }
+            ("write", JsonValue::Null) => write = Some(None),
+            ("rb", JsonValue::Number(n)) => relative_base = Some(Some(n)),
+            ("rb", JsonValue::Null) => relative_base = Some(None),
+            (key, _) => return Err(format!("unexpected value of {}", key)),
+        }
+
+        rest = after_value.trim_start();
+        rest = rest.strip_prefix(',').unwrap_or(rest).trim_start();
+    }
+
+    let opcode = opcode.ok_or("missing op")?;
+    let args = args.ok_or("missing args")?;
+
+    Ok(TraceStep {
+        ip: ip.ok_or("missing ip")?,
+        instruction: Instruction::from_parts(opcode, &args).ok_or("invalid args")?,
+        write: write.ok_or("missing write")?,
+        relative_base: relative_base.ok_or("missing rb")?,
+    })
+}
+
+fn parse_json_string(s: &str) -> Result<(String, &str), String> {
+    let s = s.strip_prefix('"').ok_or("expected a string")?;
+    let end = s.find('"').ok_or("unterminated string")?;
+
+    Ok((s[..end].to_string(), &s[end + 1..]))
+}
+
+fn parse_json_value(s: &str) -> Result<(JsonValue, &str), String> {
+    if let Some(rest) = s.strip_prefix("null") {
+        Ok((JsonValue::Null, rest))
+    } else if s.starts_with('"') {
+        parse_json_string(s).map(|(v, rest)| (JsonValue::String(v), rest))
+    } else if let Some(rest) = s.strip_prefix('[') {
+        let end = rest.find(']').ok_or("unterminated array")?;
+        let items = rest[..end]
+            .split(',')
+            .map(str::trim)
+            .filter(|i| !i.is_empty())
+            .map(|i| i.parse().map_err(|_| format!("invalid number {}", i)))
+            .collect::<Result<_, _>>()?;
+
+        Ok((JsonValue::Array(items), &rest[end + 1..]))
+    } else {
+        let end = s
+            .find(|c: char| c != '-' && !c.is_ascii_digit())
+            .unwrap_or(s.len());
+        let n = s[..end]
+            .parse()
+            .map_err(|_| format!("invalid value {}", s))?;
+
+        Ok((JsonValue::Number(n), &s[end..]))
+    }
+}
+
+const BINARY_MAGIC: &[u8; 4] = b"ICT1";
+const HAS_WRITE: u8 = 1;
+const HAS_RELATIVE_BASE: u8 = 2;
+
+/// Writes steps in a compact binary format. Each step is stored as the instruction pointer, the
+/// opcode, the arguments, a flags byte and the optional write and relative base. Integers are
+/// stored as LEB128 varints, signed ones zigzag-encoded.
+///
+/// Write errors are kept until `flush` is called, which reports the first one.
+pub struct BinaryTracer<W: Write> {
+    writer: W,
+    header_written: bool,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> BinaryTracer<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            header_written: false,
+            error: None,
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        self.write_header()?;
+
+        self.writer.flush()
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        if !self.header_written {
+            self.writer.write_all(BINARY_MAGIC)?;
+            self.header_written = true;
+        }
+
+        Ok(())
+    }
+
+    fn write_step(&mut self, step: &TraceStep) -> io::Result<()> {
+        self.write_header()?;
+
+        let mut buf = Vec::with_capacity(16);
+        write_varint(&mut buf, step.ip as u64);
+        buf.push(step.instruction.opcode().code() as u8);
+        for arg in step.instruction.args() {
+            write_varint(&mut buf, zigzag(arg));
+        }
+
+        let mut flags = 0;
+        if step.write.is_some() {
+            flags |= HAS_WRITE;
+        }
+        if step.relative_base.is_some() {
+            flags |= HAS_RELATIVE_BASE;
+        }
+        buf.push(flags);
+
+        if let Some((addr, value)) = step.write {
+            write_varint(&mut buf, addr as u64);
+            write_varint(&mut buf, zigzag(value));
+        }
+        if let Some(rb) = step.relative_base {
+            write_varint(&mut buf, zigzag(rb));
+        }
+
+        self.writer.write_all(&buf)
+    }
+}
+
+impl<W: Write> Tracer for BinaryTracer<W> {
+    fn record(&mut self, step: &TraceStep) {
+        if self.error.is_none() {
+            self.error = self.write_step(step).err();
+        }
+    }
+}
+
+pub fn read_binary(mut reader: impl Read) -> Result<Vec<TraceStep>, TraceError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    let malformed = |step: usize, message: &str| TraceError::Malformed {
+        step,
+        message: message.to_string(),
+    };
+
+    let mut bytes = bytes
+        .strip_prefix(BINARY_MAGIC)
+        .ok_or_else(|| malformed(0, "missing header"))?
+        .iter()
+        .cloned()
+        .peekable();
+    let mut steps = Vec::new();
+
+    while bytes.peek().is_some() {
+        let index = steps.len();
+        let mut next_varint =
+            || read_varint(&mut bytes).ok_or_else(|| malformed(index, "truncated"));
+
+        let ip = next_varint()? as usize;
+        let code = next_varint()? as isize;
+        let opcode = Opcode::from_code(code).ok_or_else(|| malformed(index, "invalid opcode"))?;
+        let args = (0..opcode.args_count())
+            .map(|_| next_varint().map(unzigzag))
+            .collect::<Result<Vec<_>, _>>()?;
+        let flags = next_varint()? as u8;
+
+        let write = if flags & HAS_WRITE != 0 {
+            Some((next_varint()? as usize, unzigzag(next_varint()?)))
+        } else {
+            None
+        };
+        let relative_base = if flags & HAS_RELATIVE_BASE != 0 {
+            Some(unzigzag(next_varint()?))
+        } else {
+            None
+        };
+
+        steps.push(TraceStep {
+            ip,
+            instruction: Instruction::from_parts(opcode, &args)
+                .ok_or_else(|| malformed(index, "invalid arguments"))?,
+            write,
+            relative_base,
+        });
+    }
+
+    Ok(steps)
+}
+
+fn zigzag(v: isize) -> u64 {
+    let v = v as i64;
+    ((v << 1) ^ (v >> 63)) as u64
+}
+
+fn unzigzag(v: u64) -> isize {
+    ((v >> 1) as i64 ^ -((v & 1) as i64)) as isize
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut v: u64) {
+    loop {
+        let byte = (v & 0x7f) as u8;
+        v >>= 7;
+        if v == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+fn read_varint(bytes: &mut impl Iterator<Item = u8>) -> Option<u64> {
+    let mut v = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = bytes.next()?;
+        v |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(v);
+        }
+    }
+
+    None
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MachineState {
+    pub ip: usize,
+    pub relative_base: isize,
+    /// Allocated pages of memory, by the address of their first cell.
+    pub memory: BTreeMap<usize, Vec<isize>>,
+}
+
+impl MachineState {
+    /// The value of a memory cell, 0 if it was never written.
+    pub fn read(&self, addr: usize) -> isize {
+        let start = addr - addr % PAGE_SIZE;
+        self.memory
+            .get(&start)
+            .map_or(0, |cells| cells[addr - start])
+    }
+}
+
+/// Reconstructs the state of a machine from the program it ran and a trace of its steps.
+pub struct Replayer {
+    program: Vec<isize>,
+    steps: Vec<TraceStep>,
+}
+
+impl Replayer {
+    pub fn new(program: Vec<isize>, steps: Vec<TraceStep>) -> Self {
+        Self { program, steps }
+    }
+
+    pub fn steps(&self) -> &[TraceStep] {
+        &self.steps
+    }
+
+    /// The state right before executing the step with the given index. An index equal to the
+    /// number of steps gives the state after the last step.
+    pub fn state_at(&self, step_index: usize) -> Option<MachineState> {
+        if step_index > self.steps.len() {
+            return None;
+        }
+
+        // Pages are only allocated where the trace writes, whatever the addresses
+        let mut ram = RAM::new(self.program.clone());
+        let mut ip = 0;
+        let mut relative_base = 0;
+
+        for step in &self.steps[..step_index] {
+            if let Some((addr, value)) = step.write {
+                ram.poke(addr, value);
+            }
+            if let Some(rb) = step.relative_base {
+                relative_base = rb;
+            }
+            ip = step.next_ip();
+        }
+
+        Some(MachineState {
+            ip,
+            relative_base,
+            memory: ram.pages(),
+        })
+    }
+}
+
+/// Index of the first step that differs between two traces. Returns `None` if the traces are
+/// identical.
+pub fn first_divergence(a: &[TraceStep], b: &[TraceStep]) -> Option<usize> {
+    a.iter()
+        .zip(b.iter())
+        .position(|(a, b)| a != b)
+        .or_else(|| {
+            if a.len() != b.len() {
+                Some(a.len().min(b.len()))
+            } else {
+                None
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Computer;
+
+    // Outputs 1 if the input is 8, 0 otherwise
+    const EQUALS_8: [isize; 11] = [3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+
+    fn trace(program: &[isize], input: Vec<isize>) -> Vec<TraceStep> {
+        let steps = Arc::new(Mutex::new(Vec::new()));
+        let mut computer = Computer::new(program.to_vec(), input);
+        computer.set_tracer(Arc::clone(&steps));
+        computer.run_till_halt().unwrap();
+
+        let steps = steps.lock().unwrap().clone();
+        steps
+    }
+
+    #[test]
+    fn records_steps() {
+        let steps = trace(&[109, 3, 21101, 2, 3, 0, 204, 0, 99], vec![]);
+
+        assert_eq!(
+            steps,
+            vec![
+                TraceStep {
+                    ip: 0,
+                    instruction: Instruction::AdjustRelativeBase { change: 3 },
+                    write: None,
+                    relative_base: Some(3),
+                },
+                TraceStep {
+                    ip: 2,
+                    instruction: Instruction::Add {
+                        arg1: 2,
+                        arg2: 3,
+                        out: 3
+                    },
+                    write: Some((3, 5)),
+                    relative_base: None,
+                },
+                TraceStep {
+                    ip: 6,
+                    instruction: Instruction::WriteOutput { val: 5 },
+                    write: None,
+                    relative_base: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn json_lines_round_trip() {
+        let steps = trace(&[109, -3, 21101, 2, 3, 5, 1106, 0, 9, 99], vec![]);
+        let mut tracer = JsonLinesTracer::new(Vec::new());
+        steps.iter().for_each(|s| tracer.record(s));
+        tracer.flush().unwrap();
+
+        let text = String::from_utf8(tracer.writer).unwrap();
+        assert_eq!(
+            text.lines().next(),
+            Some(r#"{"ip":0,"op":"arb","args":[-3],"write":null,"rb":-3}"#)
+        );
+        assert_eq!(read_json_lines(text.as_bytes()).unwrap(), steps);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let steps = trace(
+            &[
+                109,
+                -3,
+                21101,
+                2,
+                3,
+                5,
+                1106,
+                0,
+                9,
+                104,
+                1125899906842624,
+                99,
+            ],
+            vec![],
+        );
+        let mut tracer = BinaryTracer::new(Vec::new());
+        steps.iter().for_each(|s| tracer.record(s));
+        tracer.flush().unwrap();
+
+        assert_eq!(read_binary(&tracer.writer[..]).unwrap(), steps);
+        assert!(read_binary(&tracer.writer[..tracer.writer.len() - 1]).is_err());
+        assert!(read_binary(&b"nope"[..]).is_err());
+    }
+
+    #[test]
+    fn replays_state() {
+        let program = EQUALS_8.to_vec();
+        let steps = trace(&program, vec![8]);
+        let replayer = Replayer::new(program.clone(), steps);
+
+        assert_eq!(
+            replayer.state_at(0),
+            Some(MachineState {
+                ip: 0,
+                relative_base: 0,
+                memory: RAM::new(program).pages()
+            })
+        );
+        assert_eq!(
+            replayer.state_at(1),
+            Some(MachineState {
+                ip: 2,
+                relative_base: 0,
+                memory: RAM::new(vec![3, 9, 8, 9, 10, 9, 4, 9, 99, 8, 8]).pages()
+            })
+        );
+        assert_eq!(replayer.state_at(3).map(|s| s.read(9)), Some(1));
+        assert_eq!(replayer.state_at(3).map(|s| s.ip), Some(8));
+        assert_eq!(replayer.state_at(4), None);
+    }
+
+    #[test]
+    fn replays_writes_to_far_addresses() {
+        let far = usize::MAX / 2;
+        let steps = vec![TraceStep {
+            ip: 0,
+            instruction: Instruction::ReadInput { to: far },
+            write: Some((far, 5)),
+            relative_base: None,
+        }];
+        let state = Replayer::new(vec![3, 0, 99], steps).state_at(1).unwrap();
+
+        assert_eq!(state.read(far), 5);
+        assert_eq!(state.read(1), 0);
+        assert_eq!(state.memory.len(), 2);
+    }
+
+    #[test]
+    fn finds_divergence_between_runs() {
+        let a = trace(&EQUALS_8, vec![8]);
+        let b = trace(&EQUALS_8, vec![7]);
+
+        assert_eq!(first_divergence(&a, &a), None);
+        assert_eq!(first_divergence(&a, &b), Some(0));
+        assert_eq!(first_divergence(&a, &a[..2]), Some(2));
+    }
+}
@@ -0,0 +1,408 @@
+//! Ahead-of-time translation of programs to Rust source code.
+//!
+//! Every basic block found by the analysis becomes an arm of a `match` inside a loop, so jumps
+//! are a change of the current block. Indirect jumps, like returns from functions, work as long
+//! as they land on the start of a block. Programs that write into their own instructions cannot
+//! be translated and should be run with `Computer` instead.
+
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::{Display, Write},
+};
+
+use crate::{
+    analysis::{analyze, BasicBlock, SelfModifyingWrite},
+    disassembler::disassemble_instruction,
+    instruction::{ArgMode, InstructionHeader, Opcode},
+};
+
+/// Writes at or above this address fail in translated programs.
+const MEMORY_LIMIT: usize = 1 << 24;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TranslateError {
+    /// The analysis found instructions writing into the code of the program.
+    SelfModifyingCode(Vec<SelfModifyingWrite>),
+    /// Execution may reach an address that does not hold a valid instruction, so the program
+    /// probably writes it at runtime.
+    InvalidInstruction {
+        address: usize,
+    },
+    InvalidName(String),
+}
+
+impl Error for TranslateError {}
+
+impl Display for TranslateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TranslateError::SelfModifyingCode(writes) => write!(
+                f,
+                "the program modifies its own code, first at address {}",
+                writes[0].ip
+            ),
+            TranslateError::InvalidInstruction { address } => write!(
+                f,
+                "execution may reach address {}, which does not hold a valid instruction",
+                address
+            ),
+            TranslateError::InvalidName(name) => {
+                write!(f, "{} is not a valid function name", name)
+            }
+        }
+    }
+}
+
+/// Translates the program into a Rust function with the given name and the signature:
+///
+/// ```text
+/// pub fn name(
+///     input: &mut dyn FnMut() -> Option<isize>,
+///     output: &mut dyn FnMut(isize),
+/// ) -> Result<(), String>
+/// ```
+///
+/// The function runs the program until it halts. Errors are reported as messages like those of
+/// `IntcodeError`. Writes to code that the analysis could not foresee, like relative mode writes,
+/// are detected when they happen and fail.
+pub fn translate(program: &[isize], name: &str) -> Result<String, TranslateError> {
+    if !is_identifier(name) {
+        return Err(TranslateError::InvalidName(name.to_string()));
+    }
+
+    let analysis = analyze(program);
+    if !analysis.self_modifying_writes.is_empty() {
+        return Err(TranslateError::SelfModifyingCode(
+            analysis.self_modifying_writes,
+        ));
+    }
+    // Code running into a cell that is not an instruction usually means that the instruction is
+    // written at runtime, in a way the analysis cannot see
+    for block in analysis.blocks.values() {
+        let last = *block.instructions.last().unwrap();
+        let header = InstructionHeader::parse(program[last]).unwrap();
+        if falls_through(program, last, &header) && !analysis.blocks.contains_key(&block.end) {
+            return Err(TranslateError::InvalidInstruction { address: block.end });
+        }
+    }
+
+    let mut code = String::new();
+    code.push_str("#[allow(dead_code, unreachable_code, unused_mut, unused_variables)]\n");
+    writeln!(code, "pub fn {}(", name).unwrap();
+    code.push_str("    input: &mut dyn FnMut() -> Option<isize>,\n");
+    code.push_str("    output: &mut dyn FnMut(isize),\n");
+    code.push_str(") -> Result<(), String> {\n");
+    code.push_str(&helpers(&code_ranges(&analysis.blocks)));
+
+    let cells: Vec<String> = program.iter().map(|v| v.to_string()).collect();
+    writeln!(
+        code,
+        "    let mut memory: Vec<isize> = vec![{}];",
+        cells.join(", ")
+    )
+    .unwrap();
+    code.push_str("    let mut rb: isize = 0;\n");
+    code.push_str("    let mut block: usize = 0;\n\n");
+    code.push_str("    loop {\n        match block {\n");
+
+    for block in analysis.blocks.values() {
+        writeln!(code, "            {} => {{", block.start).unwrap();
+        let mut halts = false;
+        for &addr in &block.instructions {
+            let header = InstructionHeader::parse(program[addr]).unwrap();
+            writeln!(
+                code,
+                "                // {}: {}",
+                addr,
+                disassemble_instruction(&program[addr..]).unwrap_or_default()
+            )
+            .unwrap();
+            for line in translate_instruction(program, addr, &header) {
+                writeln!(code, "                {}", line).unwrap();
+            }
+            halts = header.opcode == Opcode::Halt;
+        }
+        if !halts {
+            writeln!(code, "                block = {};", block.end).unwrap();
+        }
+        code.push_str("            }\n");
+    }
+
+    code.push_str("            _ => {\n");
+    code.push_str("                return Err(format!(\n");
+    code.push_str(
+        "                    \"jump to address {} which is not the start of a translated block\",\n",
+    );
+    code.push_str("                    block\n                ))\n            }\n");
+    code.push_str("        }\n    }\n}\n");
+
+    Ok(code)
+}
+
+/// Statements executing the instruction at `addr`. Jumps set the next block and `continue`.
+fn translate_instruction(
+    program: &[isize],
+    addr: usize,
+    header: &InstructionHeader,
+) -> Vec<String> {
+    let operand = |arg_index: usize| program.get(addr + arg_index).cloned().unwrap_or(0);
+    let read = |arg_index: usize| {
+        let v = operand(arg_index);
+        match header.mode(arg_index) {
+            ArgMode::Immediate => format!("{}isize", v),
+            ArgMode::Position => format!("load(&memory, {}, {})?", v, addr),
+            ArgMode::Relative => format!("load(&memory, rb.wrapping_add({}), {})?", v, addr),
+        }
+    };
+    let target = |arg_index: usize| {
+        let v = operand(arg_index);
+        match header.mode(arg_index) {
+            ArgMode::Immediate => None,
+            ArgMode::Position => Some(format!("{}", v)),
+            ArgMode::Relative => Some(format!("rb.wrapping_add({})", v)),
+        }
+    };
+    // Values are computed before borrowing the memory mutably
+    let store = |arg_index: usize, value: String| match target(arg_index) {
+        Some(target) => vec![
+            format!("let value = {};", value),
+            format!("store(&mut memory, {}, value, {})?;", target, addr),
+        ],
+        None => vec![format!(
+            "return Err(\"argument {} of instruction at address {} is written to, \\
+             but uses immediate mode\".to_string());",
+            arg_index, addr
+        )],
+    };
+    let jump = |condition: &str| {
+        vec![
+            format!("if {} {{", condition),
+            format!("    block = jump({}, {})?;", read(2), addr),
+            "    continue;".to_string(),
+            "}".to_string(),
+        ]
+    };
+
+    match header.opcode {
+        Opcode::Add => store(3, format!("{}.wrapping_add({})", read(1), read(2))),
+        Opcode::Multiply => store(3, format!("{}.wrapping_mul({})", read(1), read(2))),
+        Opcode::ReadInput => store(
+            1,
+            format!(
+                "input().ok_or_else(|| \"no input left for instruction at address {}\".to_string())?",
+                addr
+            ),
+        ),
+        Opcode::WriteOutput => vec![format!("output({});", read(1))],
+        Opcode::JumpIfTrue => jump(&format!("{} != 0", read(1))),
+        Opcode::JumpIfFalse => jump(&format!("{} == 0", read(1))),
+        Opcode::LessThan => store(3, format!("({} < {}) as isize", read(1), read(2))),
+        Opcode::Equals => store(3, format!("({} == {}) as isize", read(1), read(2))),
+        Opcode::AdjustRelativeBase => vec![format!("rb = rb.wrapping_add({});", read(1))],
+        Opcode::Halt => vec!["return Ok(());".to_string()],
+    }
+}
+
+/// Whether execution may continue with the next instruction after the one at `addr`.
+fn falls_through(program: &[isize], addr: usize, header: &InstructionHeader) -> bool {
+    let condition = match header.mode(1) {
+        ArgMode::Immediate => Some(program[addr + 1] != 0),
+        _ => None,
+    };
+
+    match header.opcode {
+        Opcode::Halt => false,
+        Opcode::JumpIfTrue => condition != Some(true),
+        Opcode::JumpIfFalse => condition != Some(false),
+        _ => true,
+    }
+}
+
+/// Functions accessing memory, defined inside the translated function.
+fn helpers(code_ranges: &[(usize, usize)]) -> String {
+    let ranges: Vec<String> = code_ranges
+        .iter()
+        .map(|(start, end)| format!("{}..={}", start, end - 1))
+        .collect();
+    let is_code = if ranges.is_empty() {
+        "false".to_string()
+    } else {
+        format!("matches!(addr, {})", ranges.join(" | "))
+    };
+
+    format!(
+        r#"    fn address(addr: isize, ip: usize) -> Result<usize, String> {{
+        if addr < 0 {{
+            return Err(format!(
+                "instruction at address {{}} accesses negative address {{}}",
+                ip, addr
+            ));
+        }}
+        Ok(addr as usize)
+    }}
+
+    fn load(memory: &[isize], addr: isize, ip: usize) -> Result<isize, String> {{
+        Ok(memory.get(address(addr, ip)?).cloned().unwrap_or(0))
+    }}
+
+    fn store(memory: &mut Vec<isize>, addr: isize, value: isize, ip: usize) -> Result<(), String> {{
+        let addr = address(addr, ip)?;
+        if {is_code} {{
+            return Err(format!(
+                "instruction at address {{}} modifies the code at address {{}}",
+                ip, addr
+            ));
+        }}
+        if addr >= {limit} {{
+            return Err(format!(
+                "instruction at address {{}} accesses address {{}} above the memory limit",
+                ip, addr
+            ));
+        }}
+        if addr >= memory.len() {{
+            memory.resize(addr + 1, 0);
+        }}
+        memory[addr] = value;
+        Ok(())
+    }}
+
+    fn jump(destination: isize, ip: usize) -> Result<usize, String> {{
+        address(destination, ip)
+    }}
+
+"#,
+        is_code = is_code,
+        limit = MEMORY_LIMIT
+    )
+}
+
+/// Address ranges covered by the blocks, with adjacent blocks merged.
+fn code_ranges(blocks: &BTreeMap<usize, BasicBlock>) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for block in blocks.values() {
+        match ranges.last_mut() {
+            Some(last) if last.1 == block.start => last.1 = block.end,
+            _ => ranges.push((block.start, block.end)),
+        }
+    }
+
+    ranges
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf, process::Command};
+
+    use super::*;
+    use crate::{assembler::assemble, program, Computer};
+
+    fn load(day: &str) -> Vec<isize> {
+        let path = format!("{}/../{}/input.txt", env!("CARGO_MANIFEST_DIR"), day);
+
+        program::parse_from_string(&fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    fn interpret(program: &[isize], input: Vec<isize>) -> Vec<isize> {
+        let mut computer = Computer::new(program.to_vec(), input);
+        computer.run_till_halt().unwrap();
+
+        computer.output().clone()
+    }
+
+    #[test]
+    fn refuses_self_modifying_code() {
+        let program = assemble("add #1, #0, [patch]\npatch: out #5\nhlt").unwrap();
+        assert_eq!(
+            translate(&program, "patched"),
+            Err(TranslateError::SelfModifyingCode(vec![
+                SelfModifyingWrite {
+                    ip: 0,
+                    address: 4,
+                    target: 4
+                }
+            ]))
+        );
+
+        // The diagnostic program of day 5 fixes the header at address 6 before running it
+        assert_eq!(
+            translate(&load("05"), "diagnostics"),
+            Err(TranslateError::InvalidInstruction { address: 6 })
+        );
+
+        assert_eq!(
+            translate(&[99], "not valid"),
+            Err(TranslateError::InvalidName("not valid".to_string()))
+        );
+    }
+
+    /// A directory removed with its contents when dropped, even if the test fails.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+            fs::create_dir_all(&path).unwrap();
+
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn translated_boost_matches_interpreter() {
+        let boost = load("09");
+        let mut source = translate(&boost, "boost").unwrap();
+        source.push_str(
+            "
+            fn run(
+                f: fn(&mut dyn FnMut() -> Option<isize>, &mut dyn FnMut(isize)) -> Result<(), String>,
+                input: Vec<isize>,
+            ) -> Vec<isize> {
+                let mut input = input.into_iter();
+                let mut output = Vec::new();
+                f(&mut || input.next(), &mut |v| output.push(v)).unwrap();
+                output
+            }
+
+            fn main() {
+                println!(\"{:?}\", run(boost, vec![1]));
+                println!(\"{:?}\", run(boost, vec![2]));
+            }
+            ",
+        );
+
+        let dir = TempDir::new("intcode-translate");
+        fs::write(dir.0.join("main.rs"), source).unwrap();
+        let compiled = Command::new("rustc")
+            .current_dir(&dir.0)
+            .args(["--edition", "2018", "-O", "main.rs"])
+            .status()
+            .unwrap();
+        assert!(compiled.success());
+
+        let output = Command::new(dir.0.join("main")).output().unwrap();
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            format!(
+                "{:?}\n{:?}\n",
+                interpret(&boost, vec![1]),
+                interpret(&boost, vec![2])
+            )
+        );
+    }
+}
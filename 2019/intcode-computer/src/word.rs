@@ -0,0 +1,121 @@
+//! Types of the values stored in the memory of a `Computer`.
+
+use std::{
+    convert::TryFrom,
+    fmt::{Debug, Display},
+};
+
+/// A value stored in a memory cell. Implemented for `isize`, `i64` and `i128`, and for
+/// `num_bigint::BigInt` with the `bigint` feature.
+///
+/// Addresses, instruction headers and relative base changes must fit in an `isize` no matter the
+/// word type.
+pub trait Word: Clone + Eq + Ord + Debug + Display {
+    fn zero() -> Self;
+
+    fn one() -> Self;
+
+    fn from_isize(value: isize) -> Self;
+
+    /// Returns `None` if the value does not fit in an `isize`.
+    fn to_isize(&self) -> Option<isize>;
+
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+
+    fn wrapping_add(&self, other: &Self) -> Self;
+
+    fn wrapping_mul(&self, other: &Self) -> Self;
+
+    fn is_zero(&self) -> bool {
+        *self == Self::zero()
+    }
+
+    /// Values that do not fit in an `isize` become `isize::MIN` or `isize::MAX`.
+    fn to_isize_saturating(&self) -> isize {
+        self.to_isize().unwrap_or(if *self < Self::zero() {
+            isize::MIN
+        } else {
+            isize::MAX
+        })
+    }
+}
+
+macro_rules! impl_word_for_primitive {
+    ($($t:ty),*) => {
+        $(
+            impl Word for $t {
+                fn zero() -> Self {
+                    0
+                }
+
+                fn one() -> Self {
+                    1
+                }
+
+                fn from_isize(value: isize) -> Self {
+                    value as $t
+                }
+
+                fn to_isize(&self) -> Option<isize> {
+                    isize::try_from(*self).ok()
+                }
+
+                fn checked_add(&self, other: &Self) -> Option<Self> {
+                    <$t>::checked_add(*self, *other)
+                }
+
+                fn checked_mul(&self, other: &Self) -> Option<Self> {
+                    <$t>::checked_mul(*self, *other)
+                }
+
+                fn wrapping_add(&self, other: &Self) -> Self {
+                    <$t>::wrapping_add(*self, *other)
+                }
+
+                fn wrapping_mul(&self, other: &Self) -> Self {
+                    <$t>::wrapping_mul(*self, *other)
+                }
+            }
+        )*
+    };
+}
+
+impl_word_for_primitive!(isize, i64, i128);
+
+/// Arithmetic never overflows, so checked and wrapping operations give the same results.
+#[cfg(feature = "bigint")]
+impl Word for num_bigint::BigInt {
+    fn zero() -> Self {
+        Self::from(0)
+    }
+
+    fn one() -> Self {
+        Self::from(1)
+    }
+
+    fn from_isize(value: isize) -> Self {
+        Self::from(value)
+    }
+
+    fn to_isize(&self) -> Option<isize> {
+        isize::try_from(self).ok()
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        Some(self * other)
+    }
+
+    fn wrapping_add(&self, other: &Self) -> Self {
+        self + other
+    }
+
+    fn wrapping_mul(&self, other: &Self) -> Self {
+        self * other
+    }
+}